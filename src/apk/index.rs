@@ -0,0 +1,203 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use askama::Template;
+
+use crate::{apk::package::ApkPackage, utils::gzip_compression};
+
+#[derive(Template)]
+#[template(path = "APKINDEX")]
+struct ApkIndex {
+    packages: Vec<Vec<(char, String)>>,
+}
+
+/// Fields of the package in the index. Empty fields are left out.
+fn index_fields(package: &ApkPackage) -> Vec<(char, String)> {
+    let field = |key| package.field(key).unwrap_or_default().to_owned();
+    let list = |key| package.fields(key).join(" ");
+
+    let fields = [
+        ('C', package.checksum.clone()),
+        ('P', field("pkgname")),
+        ('V', field("pkgver")),
+        ('A', field("arch")),
+        ('S', package.size.to_string()),
+        ('I', field("size")),
+        ('T', field("pkgdesc")),
+        ('U', field("url")),
+        ('L', field("license")),
+        ('o', field("origin")),
+        ('m', field("maintainer")),
+        ('t', field("builddate")),
+        ('c', field("commit")),
+        ('k', field("provider_priority")),
+        ('D', list("depend")),
+        ('p', list("provides")),
+        ('i', list("install_if")),
+        ('r', list("replaces")),
+    ];
+
+    fields
+        .into_iter()
+        .filter(|(_, value)| !value.is_empty())
+        .collect()
+}
+
+/// Render the unsigned `APKINDEX.tar.gz` of each architecture, with the `description`
+/// of the repository.
+///
+/// Packages built for `noarch` are in the index of every architecture, and in the `noarch`
+/// index which is served to the architectures without a package of their own.
+pub fn get_indices(packages: &[ApkPackage], description: &str) -> Vec<(String, Vec<u8>)> {
+    let mut arches: BTreeSet<&str> = packages.iter().map(ApkPackage::arch).collect();
+    arches.insert("noarch");
+
+    let mut indices = Vec::new();
+
+    for arch in arches {
+        let mut selected: BTreeMap<_, _> = BTreeMap::new();
+
+        for package in packages {
+            if package.arch() == arch || package.arch() == "noarch" {
+                selected.insert((package.name(), package.version()), index_fields(package));
+            }
+        }
+
+        let index = ApkIndex {
+            packages: selected.into_values().collect(),
+        };
+        let index = index.render().unwrap();
+
+        let mut archive = tar::Builder::new(Vec::new());
+        append(&mut archive, "DESCRIPTION", description.as_bytes());
+        append(&mut archive, "APKINDEX", index.as_bytes());
+
+        indices.push((
+            arch.to_owned(),
+            gzip_compression(&archive.into_inner().unwrap()),
+        ));
+    }
+
+    indices
+}
+
+/// Prepend the signature segment to the `index`.
+///
+/// The `signature` of the compressed index is named after the public key `key_name`
+/// which verifies it.
+pub fn signed_index(index: &[u8], signature: &[u8], key_name: &str) -> Vec<u8> {
+    let mut archive = tar::Builder::new(Vec::new());
+    append(&mut archive, &format!(".SIGN.RSA.{key_name}"), signature);
+
+    // The segments are parts of a single tar archive, so the end of archive marker is cut.
+    let mut segment = archive.into_inner().unwrap();
+    segment.truncate(segment.len() - 1024);
+
+    [gzip_compression(&segment), index.to_vec()].concat()
+}
+
+fn append(archive: &mut tar::Builder<Vec<u8>>, path: &str, data: &[u8]) {
+    let mut header = tar::Header::new_gnu();
+    header.set_mode(0o644);
+    header.set_size(data.len() as u64);
+
+    archive.append_data(&mut header, path, data).unwrap();
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Read;
+
+    use insta::assert_snapshot;
+    use libflate::gzip::MultiDecoder;
+
+    use super::*;
+    use crate::{
+        apk::package::tests::{PKGINFO, apk},
+        package::tests::package,
+    };
+
+    fn apk_package(file: &str, pkginfo: &str) -> ApkPackage {
+        let package = package(file);
+        package.set_package_data(apk(pkginfo).0);
+
+        ApkPackage::from_package(&package).unwrap()
+    }
+
+    /// Files of the (possibly signed) index and their content.
+    fn files(index: &[u8]) -> Vec<(String, String)> {
+        let mut data = Vec::new();
+        MultiDecoder::new(index)
+            .unwrap()
+            .read_to_end(&mut data)
+            .unwrap();
+        let mut archive = tar::Archive::new(&data[..]);
+
+        archive
+            .entries()
+            .unwrap()
+            .map(|entry| {
+                let mut entry = entry.unwrap();
+                let path = entry.path().unwrap().to_string_lossy().into_owned();
+                let mut content = String::new();
+                entry.read_to_string(&mut content).unwrap();
+
+                (path, content)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_index() {
+        let mut package = apk_package("tool-1.2.0-r0.apk", PKGINFO);
+        package.checksum = "Q1dGVzdA==".to_owned();
+        package.size = 2048;
+
+        let index = ApkIndex {
+            packages: vec![index_fields(&package)],
+        };
+
+        assert_snapshot!(index.render().unwrap());
+    }
+
+    #[test]
+    fn test_indices() {
+        let x86_64 = apk_package("tool-1.2.0-r0.apk", PKGINFO);
+        let aarch64 = apk_package(
+            "tool-1.2.0-r0-aarch64.apk",
+            &PKGINFO.replace("arch = x86_64", "arch = aarch64"),
+        );
+        let noarch = apk_package(
+            "tool-doc-1.2.0-r0.apk",
+            &PKGINFO
+                .replace("pkgname = tool", "pkgname = tool-doc")
+                .replace("arch = x86_64", "arch = noarch"),
+        );
+
+        let indices = get_indices(&[x86_64, aarch64, noarch], "owner/tool stable");
+        let arches: Vec<_> = indices.iter().map(|(arch, _)| arch.as_str()).collect();
+        assert_eq!(arches, ["aarch64", "noarch", "x86_64"]);
+
+        let (_, index) = &indices[2];
+        let files = files(index);
+        assert_eq!(
+            files[0],
+            ("DESCRIPTION".to_owned(), "owner/tool stable".to_owned())
+        );
+        assert_eq!(files[1].0, "APKINDEX");
+        assert!(files[1].1.contains("P:tool\nV:1.2.0-r0\nA:x86_64\n"));
+        assert!(files[1].1.contains("P:tool-doc\n"));
+        assert!(!files[1].1.contains("A:aarch64"));
+
+        let signed = signed_index(index, b"signature", "packhub.rsa.pub");
+        let files = super::tests::files(&signed);
+        assert_eq!(
+            files[0],
+            (
+                ".SIGN.RSA.packhub.rsa.pub".to_owned(),
+                "signature".to_owned()
+            )
+        );
+        assert_eq!(files[1].0, "DESCRIPTION");
+        assert_eq!(files[2].0, "APKINDEX");
+    }
+}
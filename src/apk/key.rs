@@ -0,0 +1,62 @@
+//! RSA key with which the apk indices are signed.
+//!
+//! apk-tools only verify RSA signatures, so it is separate from the OpenPGP certificate.
+use std::{fs, path::Path};
+
+use anyhow::Result;
+use rsa::{
+    RsaPrivateKey,
+    pkcs1v15::SigningKey,
+    pkcs8::{DecodePrivateKey, EncodePrivateKey, LineEnding},
+    rand_core::OsRng,
+    signature::{SignatureEncoding, Signer},
+};
+use sequoia_openpgp::crypto::Password;
+use sha1::Sha1;
+
+/// Name of the public key, which is installed to `/etc/apk/keys` and names the signatures.
+pub const KEY_NAME: &str = "packhub.rsa.pub";
+
+/// Generate the RSA key and save it to the `path`, encrypted with the `passphrase`.
+pub fn generate_and_save_rsa_key(passphrase: &Password, path: &Path) -> Result<RsaPrivateKey> {
+    let key = RsaPrivateKey::new(&mut OsRng, 4096)?;
+
+    let pem = passphrase
+        .map(|passphrase| key.to_pkcs8_encrypted_pem(OsRng, passphrase, LineEnding::LF))?;
+
+    fs::write(path, pem.as_bytes())?;
+
+    Ok(key)
+}
+
+pub fn load_rsa_key_from_file(passphrase: &Password, path: &Path) -> Result<RsaPrivateKey> {
+    let pem = fs::read_to_string(path)?;
+    let key =
+        passphrase.map(|passphrase| RsaPrivateKey::from_pkcs8_encrypted_pem(&pem, passphrase))?;
+
+    Ok(key)
+}
+
+/// Sign the `data` with PKCS #1 v1.5 over its SHA1 digest, like `abuild-sign` does.
+pub fn rsa_sign(data: &[u8], key: &RsaPrivateKey) -> Vec<u8> {
+    SigningKey::<Sha1>::new(key.clone()).sign(data).to_vec()
+}
+
+#[cfg(test)]
+mod tests {
+    use rsa::{pkcs1v15::VerifyingKey, signature::Verifier};
+
+    use super::*;
+
+    #[test]
+    fn test_rsa_sign_and_verify() {
+        let key = RsaPrivateKey::new(&mut OsRng, 1024).unwrap();
+        let signature = rsa_sign(b"APKINDEX", &key);
+
+        let verifier = VerifyingKey::<Sha1>::new(key.to_public_key());
+        let signature = signature.as_slice().try_into().unwrap();
+
+        assert!(verifier.verify(b"APKINDEX", &signature).is_ok());
+        assert!(verifier.verify(b"APKINDEX2", &signature).is_err());
+    }
+}
@@ -0,0 +1,10 @@
+mod index;
+mod key;
+mod package;
+mod routes;
+
+pub use self::{
+    key::{KEY_NAME, generate_and_save_rsa_key, load_rsa_key_from_file, rsa_sign},
+    package::ApkPackage,
+    routes::apk_routes,
+};
@@ -0,0 +1,233 @@
+use std::io::{self, BufRead, Read};
+
+use anyhow::{Result, bail};
+use base64::{Engine, prelude::BASE64_STANDARD};
+use libflate::gzip::Decoder;
+use serde::{Deserialize, Serialize};
+use serde_json::{from_str, to_string};
+use sha1::{Digest, Sha1};
+
+use crate::{
+    package::{Data, Package},
+    utils::{PkgInfo, parse_pkginfo},
+};
+
+/// Alpine package (.apk)
+#[derive(Serialize, Deserialize, Debug)]
+pub struct ApkPackage {
+    /// Fields of the `.PKGINFO` file.
+    pub info: PkgInfo,
+    /// Identity of the package in the index: `Q1` followed by the base64 encoded
+    /// SHA1 of the control segment.
+    pub checksum: String,
+    pub size: usize,
+}
+
+impl ApkPackage {
+    /// Create a new Alpine package from a package.
+    ///
+    /// Also sets metadata of the package.
+    pub fn from_package(package: &Package) -> Result<Self> {
+        // Create the alpine package from the metadata if it is present.
+        if let Data::Metadata(metadata) = package.data() {
+            let package: ApkPackage = from_str(&metadata)?;
+
+            return Ok(package);
+        }
+
+        let Data::Package(file) = package.data() else {
+            bail!("Package data is not available");
+        };
+
+        let (checksum, info) = read_control_segment(file.open()?)?;
+
+        let apk = Self {
+            info,
+            checksum,
+            size: file.size() as usize,
+        };
+
+        let metadata = to_string(&apk)?;
+        package.set_metadata(metadata);
+
+        Ok(apk)
+    }
+
+    /// First value of the `.PKGINFO` field.
+    pub fn field(&self, key: &str) -> Option<&str> {
+        self.info.get(key)?.first().map(String::as_str)
+    }
+
+    /// Values of the `.PKGINFO` field.
+    pub fn fields(&self, key: &str) -> &[String] {
+        self.info.get(key).map(Vec::as_slice).unwrap_or_default()
+    }
+
+    pub fn name(&self) -> &str {
+        self.field("pkgname").unwrap_or_default()
+    }
+
+    pub fn version(&self) -> &str {
+        self.field("pkgver").unwrap_or_default()
+    }
+
+    /// Architecture of the package (`x86_64`, `aarch64`) or `noarch`.
+    pub fn arch(&self) -> &str {
+        self.field("arch").unwrap_or("noarch")
+    }
+
+    /// Name of the file which apk requests for the package.
+    pub fn file_name(&self) -> String {
+        format!("{}-{}.apk", self.name(), self.version())
+    }
+}
+
+/// Read the control segment of the package, returning its checksum and `.PKGINFO` file.
+///
+/// A package is a concatenation of gzip streams: the (optional) signature segment,
+/// the control segment and the data segment. Each of them compresses a part of a tar archive.
+/// The data segment isn't read.
+fn read_control_segment(mut data: impl BufRead) -> Result<(String, PkgInfo)> {
+    while !data.fill_buf()?.is_empty() {
+        let mut stream = Sha1Reader {
+            inner: &mut data,
+            sha1: Sha1::new(),
+        };
+
+        let mut info = None;
+        let mut decoder = Decoder::new(&mut stream)?;
+        let mut archive = tar::Archive::new(&mut decoder);
+
+        for entry in archive.entries()? {
+            let mut entry = entry?;
+
+            if entry.path()?.to_str() == Some(".PKGINFO") {
+                let mut content = String::new();
+                entry.read_to_string(&mut content)?;
+                info = Some(parse_pkginfo(&content));
+            }
+        }
+
+        // The checksum covers the whole gzip stream of the segment.
+        io::copy(&mut decoder, &mut io::sink())?;
+
+        if let Some(info) = info {
+            let checksum = format!("Q1{}", BASE64_STANDARD.encode(stream.sha1.finalize()));
+
+            return Ok((checksum, info));
+        }
+    }
+
+    bail!(".PKGINFO file not found")
+}
+
+/// Reader which hashes the bytes consumed from the `inner` reader.
+struct Sha1Reader<R> {
+    inner: R,
+    sha1: Sha1,
+}
+
+impl<R: BufRead> Read for Sha1Reader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let available = self.fill_buf()?;
+        let len = available.len().min(buf.len());
+        buf[..len].copy_from_slice(&available[..len]);
+        self.consume(len);
+
+        Ok(len)
+    }
+}
+
+impl<R: BufRead> BufRead for Sha1Reader<R> {
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        self.inner.fill_buf()
+    }
+
+    fn consume(&mut self, amt: usize) {
+        // The consumed bytes are still in the buffer, so it's filled without reading.
+        if let Ok(buf) = self.inner.fill_buf() {
+            self.sha1.update(&buf[..amt]);
+        }
+        self.inner.consume(amt);
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::{package::tests::package, utils::gzip_compression};
+
+    pub(crate) const PKGINFO: &str = "# Generated by abuild 3.13.0
+pkgname = tool
+pkgver = 1.2.0-r0
+pkgdesc = A tool
+url = https://example.com
+builddate = 1719792000
+packager = Buildozer <alpine-devel@lists.alpinelinux.org>
+size = 1024
+arch = x86_64
+origin = tool
+license = MIT
+depend = so:libc.musl-x86_64.so.1
+depend = git
+provides = cmd:tool=1.2.0-r0
+";
+
+    /// A tar archive of the files, without the end of archive marker like abuild makes them.
+    fn segment(files: &[(&str, &[u8])]) -> Vec<u8> {
+        let mut archive = tar::Builder::new(Vec::new());
+        for (path, data) in files {
+            let mut header = tar::Header::new_gnu();
+            header.set_size(data.len() as u64);
+            archive.append_data(&mut header, path, *data).unwrap();
+        }
+        let mut archive = archive.into_inner().unwrap();
+        archive.truncate(archive.len() - 1024);
+
+        gzip_compression(&archive)
+    }
+
+    /// Build a package with the `.PKGINFO` file.
+    ///
+    /// Returns the package and its control segment.
+    pub(crate) fn apk(pkginfo: &str) -> (Vec<u8>, Vec<u8>) {
+        let signature = segment(&[(".SIGN.RSA.builder.rsa.pub", b"signature")]);
+        let control = segment(&[(".PKGINFO", pkginfo.as_bytes())]);
+        let data = segment(&[("usr/bin/tool", b"")]);
+
+        ([signature, control.clone(), data].concat(), control)
+    }
+
+    #[test]
+    fn test_parsing() {
+        let package = package("tool-1.2.0-r0.apk");
+        let (data, control) = apk(PKGINFO);
+        package.set_package_data(data.clone());
+
+        let apk = ApkPackage::from_package(&package).unwrap();
+        assert_eq!(apk.name(), "tool");
+        assert_eq!(apk.version(), "1.2.0-r0");
+        assert_eq!(apk.arch(), "x86_64");
+        assert_eq!(apk.file_name(), "tool-1.2.0-r0.apk");
+        assert_eq!(apk.fields("depend"), ["so:libc.musl-x86_64.so.1", "git"]);
+        assert_eq!(apk.size, data.len());
+        assert_eq!(
+            apk.checksum,
+            format!("Q1{}", BASE64_STANDARD.encode(Sha1::digest(&control)))
+        );
+
+        // The package data should have been replaced by the metadata
+        assert!(matches!(package.data(), Data::Metadata(_)));
+        let apk = ApkPackage::from_package(&package).unwrap();
+        assert_eq!(apk.name(), "tool");
+    }
+
+    #[test]
+    fn test_android_package() {
+        // Android packages share the extension, but are zip archives.
+        let package = package("app-release.apk");
+        package.set_package_data(b"PK\x03\x04".to_vec());
+
+        assert!(ApkPackage::from_package(&package).is_err());
+    }
+}
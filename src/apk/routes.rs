@@ -0,0 +1,143 @@
+use anyhow::Context;
+use axum::{
+    Router,
+    extract::{Path, State},
+    http::{HeaderMap, Method},
+    response::Response,
+    routing::get,
+};
+use chrono::{DateTime, Utc};
+use tracing::warn;
+
+use crate::{
+    apk::{
+        KEY_NAME,
+        index::{get_indices, signed_index},
+        package::ApkPackage,
+    },
+    error::{AppError, PackhubError},
+    proxy::proxy_package,
+    repository::Repository,
+    response::data_response,
+    source::Forge,
+    state::AppState,
+    utils::{Dist, ReleaseChannel, Type},
+};
+
+async fn handle_index(
+    state: &AppState,
+    forge: Forge,
+    owner: &str,
+    repo: &str,
+    channel: &ReleaseChannel,
+    dist: Dist,
+    arch: &str,
+) -> Result<(Vec<u8>, Option<DateTime<Utc>>), AppError> {
+    let description = format!("{owner}/{repo} {channel}");
+    let mut repo = Repository::from_forge(forge, owner, repo, channel, state).await?;
+
+    let snapshot = repo
+        .snapshot(dist, |packages| {
+            // Android packages share the extension, so packages which can't be parsed are skipped.
+            let packages: Vec<ApkPackage> = packages
+                .iter()
+                .filter_map(|p| {
+                    ApkPackage::from_package(p)
+                        .inspect_err(|e| warn!("Skipping package {p:?} which isn't an apk: {e}"))
+                        .ok()
+                })
+                .collect();
+
+            let files = get_indices(&packages, &description)
+                .into_iter()
+                .map(|(arch, index)| {
+                    let signature = state.rsa_sign(&index);
+                    (
+                        format!("{arch}/APKINDEX.tar.gz"),
+                        signed_index(&index, &signature, KEY_NAME),
+                    )
+                })
+                .collect();
+
+            Ok(files)
+        })
+        .await?;
+
+    let index = snapshot
+        .file(&format!("{arch}/APKINDEX.tar.gz"))
+        .or_else(|| snapshot.file("noarch/APKINDEX.tar.gz"))
+        .with_context(|| {
+            PackhubError::NotFound(format!("Unknown architecture requested: {arch}"))
+        })?;
+
+    Ok((index.to_owned(), snapshot.last_modified()))
+}
+
+/// Proxy a package.
+///
+/// apk requests packages as `{name}-{version}.apk`, which is looked up in the package metadata
+/// saved when the index was rendered.
+async fn handle_package(
+    state: &AppState,
+    forge: Forge,
+    owner: &str,
+    repo: &str,
+    channel: &ReleaseChannel,
+    file: &str,
+    (method, headers): (&Method, &HeaderMap),
+) -> Result<Response, AppError> {
+    let repository = Repository::from_forge(forge, owner, repo, channel, state).await?;
+
+    let package = repository
+        .packages()
+        .filter(|package| *package.ty() == Type::Apk && package.is_metadata_available())
+        .find(|package| ApkPackage::from_package(package).is_ok_and(|apk| apk.file_name() == file))
+        .with_context(|| PackhubError::NotFound(format!("Unknown file requested: {file}")))?;
+
+    // The metadata of apk packages doesn't record their SHA256, so they aren't verified.
+    proxy_package(
+        state,
+        (forge, owner, repo),
+        None,
+        package.file_name(),
+        package.download_url(),
+        (method, headers),
+    )
+    .await
+}
+
+#[tracing::instrument(name = "APK", skip_all, fields(file = file))]
+async fn apk_file(
+    State(state): State<AppState>,
+    Path((forge, owner, repo, channel, release, arch, file)): Path<(
+        Forge,
+        String,
+        String,
+        ReleaseChannel,
+        String,
+        String,
+        String,
+    )>,
+    method: Method,
+    headers: HeaderMap,
+) -> Result<Response, AppError> {
+    // Alpine releases are named like `v3.20`, or `edge`.
+    let dist = Dist::alpine(release.trim_start_matches('v'));
+
+    if file == "APKINDEX.tar.gz" {
+        let (index, last_modified) =
+            handle_index(&state, forge, &owner, &repo, &channel, dist, &arch).await?;
+
+        Ok(data_response(index, last_modified, &headers))
+    } else {
+        let request = (&method, &headers);
+        handle_package(&state, forge, &owner, &repo, &channel, &file, request).await
+    }
+}
+
+pub fn apk_routes() -> Router<AppState> {
+    Router::new().route(
+        "/{forge}/{owner}/{repo}/{channel}/{release}/{arch}/{file}",
+        get(apk_file),
+    )
+}
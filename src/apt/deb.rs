@@ -2,31 +2,46 @@ use std::io::Read;
 use std::sync::LazyLock;
 
 use anyhow::{Context, Result, bail};
-use libflate::gzip::Decoder;
 use md5::Md5;
 use regex::Regex;
 use serde::{Deserialize, Serialize};
 use serde_json::{from_str, to_string};
-use sha1::Sha1;
-use sha2::{Sha256, Sha512};
+use tracing::warn;
 
 use crate::{
     package::{Data, Package},
-    utils::{Arch, hashsum},
+    utils::{Arch, decoder, hashsum},
 };
 
 static ARCH: LazyLock<Regex> = LazyLock::new(|| Regex::new(r#"Architecture: (\w+)"#).unwrap());
+static PACKAGE: LazyLock<Regex> = LazyLock::new(|| Regex::new(r#"(?m)^Package: (\S+)"#).unwrap());
+static SECTION: LazyLock<Regex> = LazyLock::new(|| Regex::new(r#"(?m)^Section: (\S+)"#).unwrap());
+
+/// Version of the saved metadata, bumped whenever its fields change.
+///
+/// Metadata of an older version is extracted again from the package.
+const METADATA_VERSION: u32 = 2;
 
 /// Debian package (.deb)
 #[derive(Serialize, Deserialize, Debug)]
 pub struct DebianPackage {
+    /// Version of the metadata (`0` for the metadata saved before it was versioned or
+    /// without the file list).
+    #[serde(default)]
+    version: u32,
     pub control: String,
     pub md5: String,
     pub sha1: String,
     pub sha256: String,
     pub sha512: String,
     pub size: usize,
+    /// Path of the package in the pool of a channel (`{tag}/{file}`).
+    ///
+    /// Packages are shared by the channels, so the channel is only added to the indices.
     pub filename: String,
+    /// Files installed by the package (without the leading `./`).
+    #[serde(default)]
+    pub files: Vec<String>,
 }
 
 impl DebianPackage {
@@ -41,30 +56,37 @@ impl DebianPackage {
             return Ok(package);
         }
 
-        let Data::Package(data) = package.data() else {
+        let Data::Package(file) = package.data() else {
             bail!("Package data is not available");
         };
 
-        let control = read_control_file(&data)
+        let control = read_control_file(file.open()?)
             .context("Error occurred while parsing the debian control file from package")?
             .trim_end()
             .to_owned();
-        let filename = format!("pool/stable/{}/{}", package.version(), package.file_name());
-
-        let size = data.len();
-        let md5 = hashsum::<Md5>(&data);
-        let sha1 = hashsum::<Sha1>(&data);
-        let sha256 = hashsum::<Sha256>(&data);
-        let sha512 = hashsum::<Sha512>(&data);
+        let filename = format!("{}/{}", package.version(), package.file_name());
+        // Without the file list, the metadata is saved as outdated, so that it's extracted again.
+        let (version, files) = match read_file_list(file.open()?) {
+            Ok(files) => (METADATA_VERSION, files),
+            Err(e) => {
+                warn!(
+                    "Unable to read the file list of the package {:?}: {e}",
+                    package.file_name()
+                );
+                (0, Vec::new())
+            }
+        };
 
         let deb = Self {
+            version,
             control,
-            md5,
-            sha1,
-            sha256,
-            sha512,
-            size,
+            md5: file.md5().to_owned(),
+            sha1: file.sha1().to_owned(),
+            sha256: file.sha256().to_owned(),
+            sha512: file.sha512().to_owned(),
+            size: file.size() as usize,
             filename,
+            files,
         };
 
         let metadata = to_string(&deb)?;
@@ -73,48 +95,78 @@ impl DebianPackage {
         Ok(deb)
     }
 
+    /// Check if the saved `metadata` is of the current version.
+    pub fn is_current(metadata: &str) -> bool {
+        from_str::<DebianPackage>(metadata).is_ok_and(|deb| deb.version == METADATA_VERSION)
+    }
+
     /// Get the architecture for which the package is built for.
     pub fn get_arch(&self) -> Option<Arch> {
-        ARCH.captures(&self.control)
-            .unwrap()
-            .get(1)
-            .unwrap()
-            .as_str()
-            .parse()
-            .ok()
+        ARCH.captures(&self.control)?.get(1)?.as_str().parse().ok()
+    }
+
+    /// Name of the package.
+    pub fn name(&self) -> Option<&str> {
+        Some(PACKAGE.captures(&self.control)?.get(1)?.as_str())
+    }
+
+    /// The `Description` field: the short description, followed by the continuation lines
+    /// of the long description.
+    pub fn description(&self) -> Option<String> {
+        let mut lines = self.control.lines();
+        let short = lines.find_map(|line| line.strip_prefix("Description:"))?;
+
+        let mut description = short.trim_start().to_owned();
+
+        for line in lines.take_while(|line| line.starts_with([' ', '\t'])) {
+            description.push('\n');
+            description.push_str(line);
+        }
+
+        Some(description)
+    }
+
+    /// MD5 of the description, which identifies it in the `Translation` indices.
+    pub fn description_md5(&self) -> Option<String> {
+        let description = self.description()?;
+
+        Some(hashsum::<Md5>(format!("{description}\n").as_bytes()))
+    }
+
+    /// Qualified name of the package (`section/name`) used in the `Contents` indices.
+    pub fn qualified_name(&self) -> Option<String> {
+        let name = self.name()?;
+
+        match SECTION.captures(&self.control) {
+            Some(section) => Some(format!("{}/{name}", &section[1])),
+            None => Some(name.to_owned()),
+        }
     }
 }
 
-fn read_control_file(data: &[u8]) -> Result<String> {
+fn read_control_file(data: impl Read) -> Result<String> {
     let mut archive = ar::Archive::new(data);
 
     while let Some(entry_result) = archive.next_entry() {
         let mut entry = entry_result?;
-        let header = entry.header();
-        let name = String::from_utf8_lossy(header.identifier());
-        if name == "control.tar.gz" {
-            let mut data = Vec::new();
-            entry.read_to_end(&mut data)?;
-
-            // Un-compress the control.tar.gz
-            let mut decoder = Decoder::new(&data[..])?;
-            let mut data = Vec::new();
-            decoder.read_to_end(&mut data)?;
-
-            // Read the control.tar archive
-            let mut archive = tar::Archive::new(&data[..]);
-            for entry in archive.entries()? {
-                let mut entry = entry?;
-
-                let path = entry.path()?;
-                let path = path.to_str().unwrap();
-
-                if path == "./control" {
-                    let mut control = String::new();
-                    entry.read_to_string(&mut control)?;
-
-                    return Ok(control);
-                }
+        let name = String::from_utf8_lossy(entry.header().identifier()).into_owned();
+
+        let Some(compression) = name.strip_prefix("control.tar") else {
+            continue;
+        };
+
+        // Read the control.tar archive
+        let mut archive = tar::Archive::new(decoder(compression, &mut entry)?);
+        for entry in archive.entries()? {
+            let mut entry = entry?;
+
+            let path = entry.path()?;
+
+            if matches!(path.to_str(), Some("./control" | "control")) {
+                let mut control = String::new();
+                entry.read_to_string(&mut control)?;
+
+                return Ok(control);
             }
         }
     }
@@ -122,12 +174,68 @@ fn read_control_file(data: &[u8]) -> Result<String> {
     bail!("Control file not found");
 }
 
+/// Read the list of files installed by the package from its `data.tar` archive.
+fn read_file_list(data: impl Read) -> Result<Vec<String>> {
+    let mut archive = ar::Archive::new(data);
+
+    while let Some(entry_result) = archive.next_entry() {
+        let mut entry = entry_result?;
+        let name = String::from_utf8_lossy(entry.header().identifier()).into_owned();
+
+        let Some(compression) = name.strip_prefix("data.tar") else {
+            continue;
+        };
+
+        let mut files = Vec::new();
+        let mut archive = tar::Archive::new(decoder(compression, &mut entry)?);
+
+        for entry in archive.entries()? {
+            let entry = entry?;
+
+            // Directories aren't listed in the `Contents` indices.
+            if entry.header().entry_type().is_dir() {
+                continue;
+            }
+
+            let path = entry.path()?;
+            let path = path.to_string_lossy();
+            let path = path.trim_start_matches("./").trim_start_matches('/');
+
+            if !path.is_empty() {
+                files.push(path.to_owned());
+            }
+        }
+
+        return Ok(files);
+    }
+
+    // Packages without data (like meta-packages) don't install any file.
+    Ok(Vec::new())
+}
+
 #[cfg(test)]
-mod tests {
+pub(crate) mod tests {
     use std::fs::read;
 
     use super::*;
-    use crate::package::tests::package;
+    use crate::{package::tests::package, utils::gzip_compression};
+
+    /// A Debian package with the `control` file installing the `files`
+    ///
+    /// For testing purpose.
+    pub(crate) fn debian_package(control: &str, files: &[&str]) -> DebianPackage {
+        DebianPackage {
+            version: METADATA_VERSION,
+            control: control.to_owned(),
+            md5: String::new(),
+            sha1: String::new(),
+            sha256: String::new(),
+            sha512: String::new(),
+            size: 0,
+            filename: String::new(),
+            files: files.iter().map(|file| file.to_string()).collect(),
+        }
+    }
 
     #[test]
     fn test_parsing() {
@@ -139,6 +247,90 @@ mod tests {
         assert_eq!(deb.get_arch(), Some(Arch::Amd64));
     }
 
+    #[test]
+    fn test_description() {
+        let deb = debian_package(
+            "Package: tool\nDescription: A tool\n Does things.\n .\n Well.\nHomepage: https://example.com",
+            &[],
+        );
+
+        assert_eq!(
+            deb.description().unwrap(),
+            "A tool\n Does things.\n .\n Well."
+        );
+        assert_eq!(
+            deb.description_md5().unwrap(),
+            hashsum::<Md5>(b"A tool\n Does things.\n .\n Well.\n")
+        );
+    }
+
+    #[test]
+    fn test_file_list() {
+        let mut data = tar::Builder::new(Vec::new());
+        for (path, kind) in [
+            ("./", tar::EntryType::Directory),
+            ("./usr/bin/", tar::EntryType::Directory),
+            ("./usr/bin/tool", tar::EntryType::Regular),
+            ("./usr/share/doc/tool/copyright", tar::EntryType::Regular),
+        ] {
+            let mut header = tar::Header::new_gnu();
+            header.set_entry_type(kind);
+            header.set_size(0);
+            data.append_data(&mut header, path, &[][..]).unwrap();
+        }
+        let data = zstd::encode_all(&data.into_inner().unwrap()[..], 0).unwrap();
+
+        let mut deb = ar::Builder::new(Vec::new());
+        let header = ar::Header::new(b"data.tar.zst".to_vec(), data.len() as u64);
+        deb.append(&header, &data[..]).unwrap();
+
+        assert_eq!(
+            read_file_list(deb.into_inner().unwrap().as_slice()).unwrap(),
+            ["usr/bin/tool", "usr/share/doc/tool/copyright"]
+        );
+    }
+
+    #[test]
+    fn test_control_compression() {
+        let mut control = tar::Builder::new(Vec::new());
+        let mut header = tar::Header::new_gnu();
+        header.set_size(13);
+        control
+            .append_data(&mut header, "control", &b"Package: tool"[..])
+            .unwrap();
+        let control = control.into_inner().unwrap();
+
+        let mut xz = Vec::new();
+        liblzma::read::XzEncoder::new(&control[..], 6)
+            .read_to_end(&mut xz)
+            .unwrap();
+        let mut bz2 = Vec::new();
+        bzip2::read::BzEncoder::new(&control[..], bzip2::Compression::default())
+            .read_to_end(&mut bz2)
+            .unwrap();
+
+        for (name, data) in [
+            ("control.tar", control.clone()),
+            ("control.tar.gz", gzip_compression(&control)),
+            ("control.tar.xz", xz),
+            (
+                "control.tar.zst",
+                zstd::encode_all(&control[..], 0).unwrap(),
+            ),
+            ("control.tar.bz2", bz2),
+        ] {
+            let mut deb = ar::Builder::new(Vec::new());
+            let header = ar::Header::new(name.as_bytes().to_vec(), data.len() as u64);
+            deb.append(&header, &data[..]).unwrap();
+
+            assert_eq!(
+                read_control_file(deb.into_inner().unwrap().as_slice()).unwrap(),
+                "Package: tool",
+                "{name}"
+            );
+        }
+    }
+
     #[test]
     #[should_panic]
     fn test_without_data() {
@@ -160,4 +352,54 @@ mod tests {
 
         let _ = DebianPackage::from_package(&package).unwrap();
     }
+
+    #[test]
+    fn test_metadata_version() {
+        let package = package("fastfetch-linux-amd64.deb");
+        let data = read("data/fastfetch-linux-amd64.deb").unwrap();
+        package.set_package_data(data);
+
+        let _ = DebianPackage::from_package(&package).unwrap();
+        let Data::Metadata(metadata) = package.data() else {
+            panic!("Metadata not extracted");
+        };
+        assert!(DebianPackage::is_current(&metadata));
+
+        // Saved before the file list was recorded
+        let metadata = r#"{"control":"Package: tool","md5":"","sha1":"","sha256":"","sha512":"","size":0,"filename":"pool/stable/1.0.0/tool.deb"}"#;
+        assert!(!DebianPackage::is_current(metadata));
+    }
+
+    #[test]
+    fn test_corrupt_file_list() {
+        let control = {
+            let mut control = tar::Builder::new(Vec::new());
+            let mut header = tar::Header::new_gnu();
+            header.set_size(34);
+            control
+                .append_data(
+                    &mut header,
+                    "control",
+                    &b"Package: tool\nArchitecture: amd64"[..],
+                )
+                .unwrap();
+            control.into_inner().unwrap()
+        };
+        let mut deb = ar::Builder::new(Vec::new());
+        for (name, data) in [("control.tar", &control[..]), ("data.tar.zst", b"corrupt")] {
+            let header = ar::Header::new(name.as_bytes().to_vec(), data.len() as u64);
+            deb.append(&header, data).unwrap();
+        }
+
+        let package = package("tool_1.0.0_amd64.deb");
+        package.set_package_data(deb.into_inner().unwrap());
+
+        let deb = DebianPackage::from_package(&package).unwrap();
+        assert_eq!(deb.get_arch(), Some(Arch::Amd64));
+        let Data::Metadata(metadata) = package.data() else {
+            panic!("Metadata not extracted");
+        };
+        // Saved as outdated, so that the file list is read again
+        assert!(!DebianPackage::is_current(&metadata));
+    }
 }
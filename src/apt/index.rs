@@ -1,9 +1,8 @@
-use std::{collections::BTreeMap, io::Write};
+use std::collections::{BTreeMap, BTreeSet};
 
 use anyhow::Result;
 use askama::Template;
 use chrono::{DateTime, Utc};
-use libflate::gzip::{EncodeOptions, Encoder, HeaderBuilder};
 use md5::Md5;
 use sha1::Sha1;
 use sha2::{Sha256, Sha512};
@@ -11,11 +10,12 @@ use sha2::{Sha256, Sha512};
 use crate::{
     apt::deb::DebianPackage,
     package::Package,
-    utils::{Arch, ReleaseChannel, hashsum},
+    utils::{Arch, ReleaseChannel, gzip_compression, hashsum},
 };
 
 #[derive(Debug)]
 pub struct AptIndices {
+    channel: ReleaseChannel,
     packages: BTreeMap<Arch, Vec<DebianPackage>>,
     date: DateTime<Utc>,
 }
@@ -32,7 +32,16 @@ struct ReleaseIndex<'a> {
 #[derive(Template)]
 #[template(path = "Packages")]
 struct PackageIndex<'a> {
-    packages: &'a [DebianPackage],
+    /// The channel whose pool the packages are served from.
+    channel: &'a ReleaseChannel,
+    packages: Vec<&'a DebianPackage>,
+}
+
+#[derive(Template)]
+#[template(path = "Translation")]
+struct TranslationIndex<'a> {
+    /// Package name, description MD5 and description
+    descriptions: BTreeSet<(&'a str, String, String)>,
 }
 
 struct Files {
@@ -45,8 +54,19 @@ struct Files {
 }
 
 impl AptIndices {
-    pub fn new(packages: &[Package]) -> Result<AptIndices> {
-        let mut debian: BTreeMap<Arch, Vec<DebianPackage>> = BTreeMap::new();
+    /// Create the indices of the `packages` of the `channel`.
+    ///
+    /// Every architecture of `architectures` is listed in the release index, with an
+    /// empty package index when none of the packages is built for it.
+    pub fn new(
+        channel: &ReleaseChannel,
+        packages: &[Package],
+        architectures: &[Arch],
+    ) -> Result<AptIndices> {
+        let mut debian: BTreeMap<Arch, Vec<DebianPackage>> = architectures
+            .iter()
+            .map(|arch| (arch.clone(), Vec::new()))
+            .collect();
         // Find the latest date from the list of packages
         let mut date = DateTime::UNIX_EPOCH;
         for package in packages {
@@ -72,56 +92,140 @@ impl AptIndices {
                 }
             }
         }
+
+        let missing: Vec<_> = debian
+            .iter()
+            .filter(|(_, packages)| packages.is_empty())
+            .map(|(arch, _)| arch.to_string())
+            .collect();
+
+        if !missing.is_empty() {
+            tracing::info!(
+                "No packages for the architectures {}, serving empty indices",
+                missing.join(", ")
+            );
+        }
+
         Ok(AptIndices {
+            channel: channel.clone(),
             packages: debian,
             date,
         })
     }
 
+    /// Packages installable on the architecture.
+    ///
+    /// Architecture independent packages are listed in the index of every architecture too,
+    /// for the clients which don't fetch the `binary-all` index.
+    fn packages_of(&self, arch: &Arch) -> Vec<&DebianPackage> {
+        let mut packages: Vec<_> = self.packages.get(arch).into_iter().flatten().collect();
+
+        if *arch != Arch::Noarch {
+            packages.extend(self.packages.get(&Arch::Noarch).into_iter().flatten());
+        }
+
+        packages
+    }
+
     pub fn get_package_index(&self, arch: &Arch) -> String {
         let index = PackageIndex {
-            packages: self.packages.get(arch).unwrap(),
+            channel: &self.channel,
+            packages: self.packages_of(arch),
         };
         index.render().unwrap().trim().to_owned()
     }
 
-    pub fn get_release_index(&self, channel: &ReleaseChannel) -> String {
-        let date = self.date.to_rfc2822();
-
-        let mut files = vec![];
+    /// Render the index files listed in the release index.
+    ///
+    /// It returns the path of each file (relative to the distribution) and its content.
+    pub fn get_index_files(&self) -> Vec<(String, Vec<u8>)> {
+        let mut files = Vec::new();
 
         for arch in self.packages.keys() {
-            let packages = self.get_package_index(arch);
-            let packages = packages.as_bytes();
-            let packages_gz = gzip_compression(packages);
-
-            files.extend([
-                Files {
-                    sha256: hashsum::<Sha256>(packages),
-                    size: packages.len(),
-                    path: format!("main/binary-{}/Packages", arch),
-                    md5: hashsum::<Md5>(packages),
-                    sha1: hashsum::<Sha1>(packages),
-                    sha512: hashsum::<Sha512>(packages),
-                },
-                Files {
-                    sha256: hashsum::<Sha256>(&packages_gz),
-                    size: packages_gz.len(),
-                    path: format!("main/binary-{}/Packages.gz", arch),
-                    md5: hashsum::<Md5>(&packages_gz),
-                    sha1: hashsum::<Sha1>(&packages_gz),
-                    sha512: hashsum::<Sha512>(&packages_gz),
-                },
-            ]);
+            let packages = self.get_package_index(arch).into_bytes();
+            let packages_gz = gzip_compression(&packages);
+
+            files.push((format!("main/binary-{arch}/Packages"), packages));
+            files.push((format!("main/binary-{arch}/Packages.gz"), packages_gz));
+
+            let contents = self.get_contents_index(arch);
+            files.push((
+                format!("main/Contents-{arch}.gz"),
+                gzip_compression(contents.as_bytes()),
+            ));
         }
 
+        let translation = self.get_translation_index().into_bytes();
+        let translation_gz = gzip_compression(&translation);
+
+        files.push(("main/i18n/Translation-en".to_owned(), translation));
+        files.push(("main/i18n/Translation-en.gz".to_owned(), translation_gz));
+
+        files
+    }
+
+    /// Render the English `Translation` index with the descriptions of the packages of every architecture.
+    pub fn get_translation_index(&self) -> String {
+        let descriptions = self
+            .packages
+            .values()
+            .flatten()
+            .filter_map(|package| {
+                Some((
+                    package.name()?,
+                    package.description_md5()?,
+                    package.description()?,
+                ))
+            })
+            .collect();
+
+        let index = TranslationIndex { descriptions };
+        index.render().unwrap().trim().to_owned()
+    }
+
+    /// Render the `Contents` index of the architecture, mapping each installed file to its packages.
+    pub fn get_contents_index(&self, arch: &Arch) -> String {
+        let mut contents: BTreeMap<&str, Vec<String>> = BTreeMap::new();
+
+        for package in self.packages_of(arch) {
+            let Some(name) = package.qualified_name() else {
+                continue;
+            };
+
+            for file in &package.files {
+                contents.entry(file).or_default().push(name.clone());
+            }
+        }
+
+        contents
+            .into_iter()
+            .map(|(file, packages)| format!("{file} {}\n", packages.join(",")))
+            .collect()
+    }
+
+    /// Render the release index listing the package index `files`.
+    pub fn get_release_index(&self, files: &[(String, Vec<u8>)]) -> String {
+        let date = self.date.to_rfc2822();
+
+        let mut files: Vec<Files> = files
+            .iter()
+            .map(|(path, data)| Files {
+                sha256: hashsum::<Sha256>(data),
+                size: data.len(),
+                path: path.clone(),
+                md5: hashsum::<Md5>(data),
+                sha1: hashsum::<Sha1>(data),
+                sha512: hashsum::<Sha512>(data),
+            })
+            .collect();
+
         // Sort the files
         files.sort_by(|a, b| a.path.cmp(&b.path));
 
         let index = ReleaseIndex {
             date,
             files,
-            channel,
+            channel: &self.channel,
             arch: self.packages.keys().map(|s| s.to_string()).collect(),
         };
 
@@ -129,17 +233,6 @@ impl AptIndices {
     }
 }
 
-pub fn gzip_compression(data: &[u8]) -> Vec<u8> {
-    let header = HeaderBuilder::new().modification_time(0).finish();
-    let options = EncodeOptions::new().header(header);
-    let mut encoder = Encoder::with_options(Vec::new(), options).unwrap();
-    encoder.write_all(data).unwrap();
-
-    let gzip = encoder.finish();
-
-    gzip.into_result().unwrap()
-}
-
 #[cfg(test)]
 mod tests {
     use std::fs::{self, read};
@@ -148,7 +241,7 @@ mod tests {
     use insta::assert_snapshot;
 
     use super::*;
-    use crate::package::tests::package_with_ver;
+    use crate::{apt::deb::tests::debian_package, package::tests::package_with_ver};
 
     #[test]
     fn test_apt_indices() {
@@ -158,14 +251,14 @@ mod tests {
 
         let packages = vec![package];
 
-        let indices = AptIndices::new(&packages).unwrap();
+        let indices = AptIndices::new(&ReleaseChannel::Stable, &packages, &[]).unwrap();
 
         // Packages
         let packages = indices.get_package_index(&Arch::Amd64);
         assert_snapshot!(packages);
 
         // Release
-        let release = indices.get_release_index(&ReleaseChannel::Stable);
+        let release = indices.get_release_index(&indices.get_index_files());
         assert_snapshot!(release);
     }
 
@@ -181,17 +274,17 @@ mod tests {
 
         let packages = vec![package1, package2];
 
-        let indices = AptIndices::new(&packages).unwrap();
+        let indices = AptIndices::new(&ReleaseChannel::Stable, &packages, &[]).unwrap();
 
         // Packages
         let packages = indices.get_package_index(&Arch::Amd64);
         assert_snapshot!(packages);
-        assert_eq!(packages.as_bytes().len(), 2729);
+        assert_eq!(packages.len(), 2729);
         let packages_gz = gzip_compression(packages.as_bytes());
         assert_eq!(packages_gz.len(), 1105);
 
         // Release
-        let release = indices.get_release_index(&ReleaseChannel::Stable);
+        let release = indices.get_release_index(&indices.get_index_files());
         assert_snapshot!(release);
     }
 
@@ -229,10 +322,152 @@ mod tests {
             package1, package2, package3, package4, package5, package6, package7,
         ];
 
-        let indices = AptIndices::new(&packages).unwrap();
+        let indices = AptIndices::new(&ReleaseChannel::Stable, &packages, &[]).unwrap();
 
         // Release
-        let release = indices.get_release_index(&ReleaseChannel::Stable);
+        let release = indices.get_release_index(&indices.get_index_files());
         assert_snapshot!(release);
     }
+
+    #[test]
+    fn test_contents() {
+        let package1 = package_with_ver("tool_1.0.0_amd64.deb", "1.0.0");
+        let metadata = debian_package(
+            "Package: tool\nSection: utils\nArchitecture: amd64",
+            &["usr/bin/tool", "usr/share/doc/tool/copyright"],
+        );
+        package1.set_metadata(serde_json::to_string(&metadata).unwrap());
+
+        let package2 = package_with_ver("tool-data_1.0.0_amd64.deb", "1.0.0");
+        let metadata = debian_package(
+            "Package: tool-data\nArchitecture: amd64",
+            &["usr/share/doc/tool/copyright", "usr/share/tool/data"],
+        );
+        package2.set_metadata(serde_json::to_string(&metadata).unwrap());
+
+        let indices = AptIndices::new(&ReleaseChannel::Stable, &[package1, package2], &[]).unwrap();
+
+        assert_eq!(
+            indices.get_contents_index(&Arch::Amd64),
+            "usr/bin/tool utils/tool\n\
+             usr/share/doc/tool/copyright utils/tool,tool-data\n\
+             usr/share/tool/data tool-data\n"
+        );
+    }
+
+    #[test]
+    fn test_translation() {
+        let package = package_with_ver("fastfetch-linux-amd64.deb", "2.40.3");
+        let data = fs::read("data/fastfetch-linux-amd64.deb").unwrap();
+        package.set_package_data(data);
+
+        let indices = AptIndices::new(&ReleaseChannel::Stable, &[package], &[]).unwrap();
+
+        let packages = indices.get_package_index(&Arch::Amd64);
+        let translation = indices.get_translation_index();
+        assert_snapshot!(translation);
+
+        // Both indices refer to the description by the same MD5
+        let md5 = translation
+            .lines()
+            .find_map(|line| line.strip_prefix("Description-md5: "))
+            .unwrap();
+        assert!(packages.contains(&format!("Description-md5: {md5}")));
+    }
+
+    #[test]
+    fn test_multiple_versions() {
+        let package1 = package_with_ver("fastfetch-linux-amd64.deb", "2.40.3");
+        let data = fs::read("data/fastfetch-linux-amd64.deb").unwrap();
+        package1.set_package_data(data.clone());
+
+        let package2 = package_with_ver("fastfetch-linux-amd64.deb", "2.40.0");
+        package2.set_package_data(data);
+
+        let packages = [package1, package2];
+        let indices = AptIndices::new(&ReleaseChannel::Stable, &packages, &[]).unwrap();
+
+        // Every version is listed in its own pool directory
+        let index = indices.get_package_index(&Arch::Amd64);
+        assert!(index.contains("Filename: pool/stable/2.40.3/fastfetch-linux-amd64.deb"));
+        assert!(index.contains("Filename: pool/stable/2.40.0/fastfetch-linux-amd64.deb"));
+
+        // In the pool of the channel
+        let indices = AptIndices::new(&ReleaseChannel::Unstable, &packages, &[]).unwrap();
+        let index = indices.get_package_index(&Arch::Amd64);
+        assert!(index.contains("Filename: pool/unstable/2.40.3/fastfetch-linux-amd64.deb"));
+        assert!(index.contains("Filename: pool/unstable/2.40.0/fastfetch-linux-amd64.deb"));
+    }
+
+    #[test]
+    fn test_architectures_without_packages() {
+        let package = package_with_ver("fastfetch-linux-amd64.deb", "2.40.3");
+        let data = fs::read("data/fastfetch-linux-amd64.deb").unwrap();
+        package.set_package_data(data);
+
+        let indices = AptIndices::new(
+            &ReleaseChannel::Stable,
+            &[package],
+            &[Arch::Amd64, Arch::Arm64],
+        )
+        .unwrap();
+
+        // The architecture without packages has a valid, empty index
+        assert_eq!(indices.get_package_index(&Arch::Arm64), "");
+        assert_eq!(indices.get_contents_index(&Arch::Arm64), "");
+        assert!(!indices.get_package_index(&Arch::Amd64).is_empty());
+
+        let files = indices.get_index_files();
+        let release = indices.get_release_index(&files);
+        assert!(release.contains("Architectures: amd64 arm64\n"));
+        assert!(release.contains(" main/binary-arm64/Packages\n"));
+        assert!(release.contains(" main/binary-arm64/Packages.gz\n"));
+
+        // Without any package at all
+        let indices = AptIndices::new(&ReleaseChannel::Stable, &[], &[Arch::Amd64]).unwrap();
+        assert_eq!(indices.get_package_index(&Arch::Amd64), "");
+    }
+
+    #[test]
+    fn test_architecture_independent_packages() {
+        let package1 = package_with_ver("tool_1.0.0_amd64.deb", "1.0.0");
+        let metadata = debian_package(
+            "Package: tool\nArchitecture: amd64",
+            &["usr/share/tool/data"],
+        );
+        package1.set_metadata(serde_json::to_string(&metadata).unwrap());
+
+        let package2 = package_with_ver("tool-data_1.0.0_all.deb", "1.0.0");
+        let metadata = debian_package(
+            "Package: tool-data\nArchitecture: all",
+            &["usr/share/tool/data"],
+        );
+        package2.set_metadata(serde_json::to_string(&metadata).unwrap());
+
+        let indices = AptIndices::new(
+            &ReleaseChannel::Stable,
+            &[package1, package2],
+            &[Arch::Amd64, Arch::Arm64],
+        )
+        .unwrap();
+
+        let all = indices.get_package_index(&Arch::Noarch);
+        assert!(all.contains("Package: tool-data\n"));
+        assert!(!all.contains("Package: tool\n"));
+
+        // Listed in the index of every architecture too
+        let amd64 = indices.get_package_index(&Arch::Amd64);
+        assert!(amd64.contains("Package: tool\n"));
+        assert!(amd64.contains("Package: tool-data\n"));
+
+        let arm64 = indices.get_package_index(&Arch::Arm64);
+        assert!(!arm64.contains("Package: tool\n"));
+        assert!(arm64.contains("Package: tool-data\n"));
+
+        let files = indices.get_index_files();
+        let release = indices.get_release_index(&files);
+        assert!(release.contains("Architectures: amd64 arm64 all\n"));
+        assert!(release.contains(" main/binary-all/Packages\n"));
+        assert!(release.contains(" main/Contents-all.gz\n"));
+    }
 }
@@ -3,5 +3,4 @@ mod index;
 mod routes;
 
 pub use self::routes::apt_routes;
-#[cfg(test)]
 pub use deb::DebianPackage;
@@ -1,136 +1,313 @@
-use anyhow::{Context, anyhow};
+use anyhow::{Context, Result};
 use axum::{
     Router,
-    body::Body,
     extract::{Path, State},
-    response::IntoResponse,
+    http::{HeaderMap, Method},
+    response::Response,
     routing::get,
 };
 use axum_extra::{headers::UserAgent, typed_header::TypedHeader};
+use serde::Deserialize;
 
 use crate::{
-    REQWEST,
-    apt::index::{AptIndices, gzip_compression},
-    error::AppError,
+    apt::index::AptIndices,
+    error::{AppError, PackhubError},
+    proxy::{package_response, release_asset},
     repository::Repository,
+    response::data_response,
+    snapshot::{Snapshot, snapshot_collection},
+    source::Forge,
     state::AppState,
-    utils::{Arch, ReleaseChannel},
+    utils::{Arch, ReleaseChannel, gzip_compression},
 };
 
+/// Path of an apt repository.
+#[derive(Deserialize)]
+struct RepoPath {
+    distro: String,
+    /// Release of the distribution (e.g. `24.04` or `noble`). When it's absent,
+    /// the release is detected from the user-agent of apt.
+    release: Option<String>,
+    forge: Forge,
+    owner: String,
+    repo: String,
+    channel: ReleaseChannel,
+}
+
+/// Path of an index file of the repository.
+#[derive(Deserialize)]
+struct FilePath {
+    file: String,
+}
+
+/// Path of a `Packages` index of the repository.
+#[derive(Deserialize)]
+struct IndexPath {
+    index: String,
+}
+
+#[derive(Deserialize)]
+struct ArchPath {
+    arch: String,
+}
+
+/// Get the snapshot of the apt index files of the repository for the client.
+async fn apt_snapshot(state: &AppState, path: &RepoPath, agent: &UserAgent) -> Result<Snapshot> {
+    let channel = &path.channel;
+    let mut repo =
+        Repository::from_forge(path.forge, &path.owner, &path.repo, channel, state).await?;
+    let dist = repo.detect_apt_dist(&path.distro, path.release.as_deref(), agent.as_str())?;
+
+    repo.snapshot(dist, |packages| {
+        let index = AptIndices::new(channel, packages, state.apt_architectures())?;
+        let mut files = index.get_index_files();
+
+        let release_file = index.get_release_index(&files);
+
+        files.push((
+            "Release.gpg".to_owned(),
+            state.detached_sign_metadata(&release_file)?,
+        ));
+        files.push((
+            "InRelease".to_owned(),
+            state.clearsign_metadata(&release_file)?,
+        ));
+        files.push(("Release".to_owned(), release_file.into_bytes()));
+
+        Ok(files)
+    })
+    .await
+}
+
 #[tracing::instrument(name = "Debian Release File", skip_all, fields(agent = agent.as_str()))]
 async fn release_index(
     State(state): State<AppState>,
-    Path((distro, owner, repo, channel, file)): Path<(
-        String,
-        String,
-        String,
-        ReleaseChannel,
-        String,
-    )>,
+    Path(path): Path<RepoPath>,
+    Path(FilePath { file }): Path<FilePath>,
     TypedHeader(agent): TypedHeader<UserAgent>,
-) -> Result<Vec<u8>, AppError> {
-    let mut repo = Repository::from_github(&owner, &repo, &channel, &state).await;
-    let packages = repo.select_package_apt(&distro, agent.as_str()).await?;
-
-    let index = AptIndices::new(&packages)?;
-    repo.save_package_metadata().await;
-
-    let release_file = index.get_release_index(&channel);
+    headers: HeaderMap,
+) -> Result<Response, AppError> {
+    let snapshot = apt_snapshot(&state, &path, &agent).await?;
 
     match file.as_str() {
-        "Release" => Ok(release_file.into_bytes()),
-        "Release.gpg" => {
-            let signed_release_file = state.detached_sign_metadata(&release_file)?;
-            Ok(signed_release_file)
-        }
-        "InRelease" => {
-            let signed_release_file = state.clearsign_metadata(&release_file)?;
-            Ok(signed_release_file)
+        "Release" | "Release.gpg" | "InRelease" => {
+            let data = snapshot.file(&file).unwrap().to_owned();
+            Ok(data_response(data, snapshot.last_modified(), &headers))
         }
-        file => Err(anyhow!("Unknown file requested: {file}").into()),
+        file => Err(PackhubError::NotFound(format!("Unknown file requested: {file}")).into()),
     }
 }
 
 #[tracing::instrument(name = "Debian Package metadata file", skip_all, fields(agent = agent.as_str()))]
 async fn packages_file(
     State(state): State<AppState>,
-    Path((distro, owner, repo, channel, arch, file)): Path<(
-        String,
-        String,
-        String,
-        ReleaseChannel,
-        String,
-        String,
-    )>,
+    Path(path): Path<RepoPath>,
+    Path(ArchPath { arch }): Path<ArchPath>,
+    Path(IndexPath { index: file }): Path<IndexPath>,
     TypedHeader(agent): TypedHeader<UserAgent>,
-) -> Result<Vec<u8>, AppError> {
-    let mut repo = Repository::from_github(&owner, &repo, &channel, &state).await;
-    let packages = repo.select_package_apt(&distro, agent.as_str()).await?;
+    headers: HeaderMap,
+) -> Result<Response, AppError> {
+    let Ok(arch) = arch.parse::<Arch>() else {
+        return Err(PackhubError::NotFound(format!("Unknown architecture: {arch}")).into());
+    };
+
+    let snapshot = apt_snapshot(&state, &path, &agent).await?;
+
+    if !matches!(file.as_str(), "Packages" | "Packages.gz") {
+        return Err(PackhubError::NotFound(format!("Unknown file requested: {file}")).into());
+    }
+
+    let index = match snapshot.file(&format!("main/binary-{arch}/{file}")) {
+        Some(index) => index.to_owned(),
+        None => {
+            // The architecture isn't listed in the release index of the snapshot.
+            tracing::info!(
+                "No packages of {}/{} for the architecture {arch}, serving an empty index",
+                path.owner,
+                path.repo
+            );
+            empty_packages(&file)?
+        }
+    };
 
-    let index = AptIndices::new(&packages)?;
-    repo.save_package_metadata().await;
+    Ok(data_response(index, snapshot.last_modified(), &headers))
+}
+
+#[tracing::instrument(name = "Debian Contents file", skip_all, fields(agent = agent.as_str()))]
+async fn contents_file(
+    State(state): State<AppState>,
+    Path(path): Path<RepoPath>,
+    Path(FilePath { file }): Path<FilePath>,
+    TypedHeader(agent): TypedHeader<UserAgent>,
+    headers: HeaderMap,
+) -> Result<Response, AppError> {
+    let Some(arch) = file
+        .strip_prefix("Contents-")
+        .and_then(|file| file.strip_suffix(".gz"))
+    else {
+        return Err(PackhubError::NotFound(format!("Unknown file requested: {file}")).into());
+    };
 
     let Ok(arch) = arch.parse::<Arch>() else {
-        return Err(anyhow!("Unknown architecture: {arch}").into());
+        return Err(PackhubError::NotFound(format!("Unknown architecture: {arch}")).into());
     };
 
-    match file.as_str() {
-        "Packages" => Ok(index.get_package_index(&arch).as_bytes().to_owned()),
-        "Packages.gz" => Ok(gzip_compression(index.get_package_index(&arch).as_bytes())),
-        file => Err(anyhow!("Unknown file requested: {file}").into()),
+    let snapshot = apt_snapshot(&state, &path, &agent).await?;
+
+    let contents = match snapshot.file(&format!("main/Contents-{arch}.gz")) {
+        Some(contents) => contents.to_owned(),
+        None => {
+            tracing::info!(
+                "No packages of {}/{} for the architecture {arch}, serving an empty index",
+                path.owner,
+                path.repo
+            );
+            gzip_compression(&[])
+        }
+    };
+
+    Ok(data_response(contents, snapshot.last_modified(), &headers))
+}
+
+#[tracing::instrument(name = "Debian Translation file", skip_all, fields(agent = agent.as_str()))]
+async fn translation_file(
+    State(state): State<AppState>,
+    Path(path): Path<RepoPath>,
+    Path(FilePath { file }): Path<FilePath>,
+    TypedHeader(agent): TypedHeader<UserAgent>,
+    headers: HeaderMap,
+) -> Result<Response, AppError> {
+    if !matches!(file.as_str(), "Translation-en" | "Translation-en.gz") {
+        return Err(PackhubError::NotFound(format!("Unknown file requested: {file}")).into());
     }
+
+    let snapshot = apt_snapshot(&state, &path, &agent).await?;
+
+    let translation = snapshot
+        .file(&format!("main/i18n/{file}"))
+        .with_context(|| PackhubError::NotFound(format!("Translation file not found: {file}")))?
+        .to_owned();
+
+    Ok(data_response(
+        translation,
+        snapshot.last_modified(),
+        &headers,
+    ))
 }
 
-async fn empty_packages_file(
-    Path((_, _, _, _, file)): Path<(String, String, String, String, String)>,
-) -> Result<Vec<u8>, AppError> {
-    match file.as_str() {
+/// Path of an index file requested by its hash.
+#[derive(Deserialize)]
+struct ByHashPath {
+    forge: Forge,
+    owner: String,
+    repo: String,
+    hash: String,
+}
+
+#[tracing::instrument(name = "Debian index file by hash", skip_all)]
+async fn index_file_by_hash(
+    State(state): State<AppState>,
+    Path(ByHashPath {
+        forge,
+        owner,
+        repo,
+        hash,
+    }): Path<ByHashPath>,
+    headers: HeaderMap,
+) -> Result<Response, AppError> {
+    let project = format!("{owner}/{repo}");
+    let collection = snapshot_collection(&state, forge);
+
+    let file = Snapshot::find_by_hash(&collection, &project, &hash)
+        .await?
+        .with_context(|| {
+            PackhubError::NotFound(format!("No index file found with the hash: {hash}"))
+        })?;
+
+    // The content of a file requested by its hash never changes.
+    Ok(data_response(file, None, &headers))
+}
+
+/// An empty, but valid, `Packages` index.
+fn empty_packages(index: &str) -> Result<Vec<u8>, AppError> {
+    match index {
         "Packages" => Ok(Vec::new()),
-        "Packages.gz" => Ok(gzip_compression(&Vec::new())),
-        file => Err(anyhow!("Unknown file requested: {file}").into()),
+        "Packages.gz" => Ok(gzip_compression(&[])),
+        file => Err(PackhubError::NotFound(format!("Unknown file requested: {file}")).into()),
     }
 }
 
+/// Path of a package in the pool of the repository.
+#[derive(Deserialize)]
+struct PoolPath {
+    forge: Forge,
+    owner: String,
+    repo: String,
+    /// The tag of the release, which can contain `/`, and the file name of the package.
+    path: String,
+}
+
 #[tracing::instrument(name = "Debian Package proxy", skip_all)]
 async fn pool(
-    Path((_, owner, repo, _channel, ver, file)): Path<(
-        String,
-        String,
-        String,
-        ReleaseChannel,
-        String,
-        String,
-    )>,
-) -> Result<impl IntoResponse, AppError> {
-    let url = format!("https://github.com/{owner}/{repo}/releases/download/{ver}/{file}");
-    tracing::trace!("Proxying package from: {}", url);
-    let res = REQWEST
-        .get(url)
-        .send()
-        .await
-        .context("Error occurred while proxying package")?;
-    tracing::trace!("Proxying package respone: {}", res.status());
-    let stream = Body::from_stream(res.bytes_stream());
-
-    Ok(stream)
+    State(state): State<AppState>,
+    Path(PoolPath {
+        forge,
+        owner,
+        repo,
+        path,
+    }): Path<PoolPath>,
+    method: Method,
+    headers: HeaderMap,
+) -> Result<Response, AppError> {
+    let (tag, file) = release_asset(&path)?;
+
+    package_response(
+        &state,
+        (forge, &owner, &repo),
+        tag,
+        file,
+        (&method, &headers),
+    )
+    .await
 }
 
-pub fn apt_routes() -> Router<AppState> {
+/// Routes of a repository, nested under the distribution and optionally its release.
+fn repository_routes() -> Router<AppState> {
     Router::new()
         .route(
-            "/{distro}/github/{owner}/{repo}/dists/{channel}/{file}",
+            "/{forge}/{owner}/{repo}/dists/{channel}/{file}",
             get(release_index),
         )
         .route(
-            "/{distro}/github/{owner}/{repo}/dists/{channel}/main/binary-{arch}/{index}",
+            "/{forge}/{owner}/{repo}/dists/{channel}/main/binary-{arch}/{index}",
             get(packages_file),
         )
         .route(
-            "/{distro}/github/{owner}/{repo}/dists/{channel}/main/binary-all/{index}",
-            get(empty_packages_file),
+            "/{forge}/{owner}/{repo}/dists/{channel}/main/binary-{arch}/by-hash/SHA256/{hash}",
+            get(index_file_by_hash),
+        )
+        .route(
+            "/{forge}/{owner}/{repo}/dists/{channel}/main/by-hash/SHA256/{hash}",
+            get(index_file_by_hash),
         )
         .route(
-            "/{distro}/github/{owner}/{repo}/pool/{channel}/{ver}/{file}",
-            get(pool),
+            "/{forge}/{owner}/{repo}/dists/{channel}/main/{file}",
+            get(contents_file),
         )
+        .route(
+            "/{forge}/{owner}/{repo}/dists/{channel}/main/i18n/by-hash/SHA256/{hash}",
+            get(index_file_by_hash),
+        )
+        .route(
+            "/{forge}/{owner}/{repo}/dists/{channel}/main/i18n/{file}",
+            get(translation_file),
+        )
+        .route("/{forge}/{owner}/{repo}/pool/{channel}/{*path}", get(pool))
+}
+
+pub fn apt_routes() -> Router<AppState> {
+    Router::new()
+        .nest("/{distro}", repository_routes())
+        // The release in the path overrides the detection from the user-agent.
+        .nest("/{distro}/{release}", repository_routes())
 }
@@ -0,0 +1,176 @@
+//! Content-addressed store of the packages, so that they can be served while the forge is unreachable.
+//!
+//! Packages are stored under their SHA256 when they are downloaded to extract their metadata.
+//! Once the store outgrows its maximum size, the least recently served packages are evicted.
+use std::{fs, io, path::PathBuf, sync::Arc, time::SystemTime};
+
+use anyhow::{Context, Result};
+use axum::{http::HeaderMap, response::Response};
+use tempfile::NamedTempFile;
+use tracing::{debug, error};
+
+use crate::{
+    db::{PackageMetadata, metadata_collection},
+    package::PackageFile,
+    response::file_response,
+    source::Forge,
+    state::AppState,
+};
+
+pub struct BlobStore {
+    dir: PathBuf,
+    /// Maximum size of the stored packages, in bytes.
+    max_size: u64,
+}
+
+impl BlobStore {
+    pub fn new(dir: impl Into<PathBuf>, max_size: u64) -> Self {
+        Self {
+            dir: dir.into(),
+            max_size,
+        }
+    }
+
+    fn path(&self, sha256: &str) -> Option<PathBuf> {
+        if sha256.len() != 64 || !sha256.bytes().all(|b| b.is_ascii_hexdigit()) {
+            return None;
+        }
+
+        Some(self.dir.join(&sha256[..2]).join(sha256))
+    }
+
+    /// Path of the package with the `sha256`, if it's stored.
+    ///
+    /// The package is marked as recently used.
+    pub fn get(&self, sha256: &str) -> Option<PathBuf> {
+        let path = self.path(sha256)?;
+        let file = fs::File::options().append(true).open(&path).ok()?;
+        _ = file.set_modified(SystemTime::now());
+
+        Some(path)
+    }
+
+    /// Store the package `file` under its SHA256.
+    pub async fn insert(self: &Arc<Self>, file: PackageFile) -> Result<()> {
+        let store = self.clone();
+
+        tokio::task::spawn_blocking(move || store.insert_blocking(&file)).await?
+    }
+
+    fn insert_blocking(&self, file: &PackageFile) -> Result<()> {
+        if self.get(file.sha256()).is_some() {
+            return Ok(());
+        }
+
+        let path = self.path(file.sha256()).context("Invalid SHA256")?;
+        let dir = path.parent().context("Invalid blob path")?;
+        fs::create_dir_all(dir)?;
+
+        // Written to a temporary file first, so that a partially written package is never served.
+        let mut blob = NamedTempFile::new_in(dir)?;
+        io::copy(&mut file.open()?, &mut blob)?;
+        blob.persist(&path)?;
+
+        debug!("Stored {file:?} as {path:?}");
+
+        self.evict()
+    }
+
+    /// Remove the least recently used packages until the store fits in its maximum size.
+    fn evict(&self) -> Result<()> {
+        let mut blobs = Vec::new();
+
+        for dir in fs::read_dir(&self.dir)? {
+            let dir = dir?;
+            if !dir.file_type()?.is_dir() {
+                continue;
+            }
+
+            for entry in fs::read_dir(dir.path())? {
+                let entry = entry?;
+                // Packages being written are temporary files starting with a dot.
+                if entry.file_name().to_string_lossy().starts_with('.') {
+                    continue;
+                }
+
+                let metadata = entry.metadata()?;
+                blobs.push((metadata.modified()?, metadata.len(), entry.path()));
+            }
+        }
+
+        let mut size: u64 = blobs.iter().map(|(_, len, _)| len).sum();
+        blobs.sort();
+
+        for (_, len, path) in blobs {
+            if size <= self.max_size {
+                break;
+            }
+
+            debug!("Evicting {path:?} from the blob store");
+            fs::remove_file(&path)?;
+            size -= len;
+        }
+
+        Ok(())
+    }
+}
+
+/// Serve the package `file` released with the version `ver` from the blob store, if it's stored there.
+///
+/// The package is found by the SHA256 recorded in its saved metadata.
+pub async fn stored_package(
+    state: &AppState,
+    (forge, owner, repo): (Forge, &str, &str),
+    ver: Option<&str>,
+    file: &str,
+    headers: &HeaderMap,
+) -> Option<Response> {
+    let blobs = state.blob_store()?;
+
+    let collection = metadata_collection(state, forge, &format!("{owner}/{repo}"));
+    let (_, sha256) = PackageMetadata::find_released(&collection, file, ver).await?;
+    let path = blobs.get(&sha256)?;
+
+    debug!("Serving {file} from the blob store");
+
+    file_response(&path, &sha256, headers)
+        .await
+        .inspect_err(|e| error!("Unable to serve {file} from the blob store: {e}"))
+        .ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_blob_store() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = Arc::new(BlobStore::new(dir.path(), 2048));
+
+        let first = PackageFile::from_bytes(&[1; 1024]);
+        let second = PackageFile::from_bytes(&[2; 1024]);
+        let third = PackageFile::from_bytes(&[3; 1024]);
+
+        store.insert(first.clone()).await.unwrap();
+        store.insert(second.clone()).await.unwrap();
+
+        let path = store.get(first.sha256()).unwrap();
+        assert_eq!(fs::read(path).unwrap(), [1; 1024]);
+        assert!(store.get("../../etc/passwd").is_none());
+
+        // The least recently used package is evicted
+        let past = SystemTime::now() - std::time::Duration::from_secs(60);
+        fs::File::options()
+            .append(true)
+            .open(store.path(second.sha256()).unwrap())
+            .unwrap()
+            .set_modified(past)
+            .unwrap();
+        store.insert(third.clone()).await.unwrap();
+
+        assert!(store.get(first.sha256()).is_some());
+        assert!(store.get(second.sha256()).is_none());
+        assert!(store.get(third.sha256()).is_some());
+    }
+}
@@ -1,8 +1,18 @@
+use anyhow::Result;
 use bson::doc;
 use chrono::{DateTime, Utc};
+use futures_util::TryStreamExt;
+use mongodb::Collection;
 use serde::{Deserialize, Serialize};
+use tracing::error;
 
-use crate::package::{Data, Package};
+use crate::{
+    apt::DebianPackage,
+    package::{Data, Package},
+    source::Forge,
+    state::AppState,
+    utils::Type,
+};
 
 #[derive(Serialize, Deserialize, PartialEq, Eq, Debug)]
 pub struct PackageMetadata {
@@ -35,7 +45,76 @@ impl PackageMetadata {
         collection
             .find_one(doc! { "name": package.file_name(), "created_at": package.creation_date() })
             .await
-            .unwrap()
+            .inspect_err(|e| error!("Unable to retrieve the metadata of {package:?}: {e}"))
+            .ok()
+            .flatten()
+    }
+
+    /// Latest saved metadata of the package `name` released with the version `ver`, along
+    /// with the SHA256 of the package.
+    ///
+    /// Only the metadata of deb, rpm and pacman packages records the SHA256 of the package.
+    pub async fn find_released(
+        collection: &Collection<PackageMetadata>,
+        name: &str,
+        ver: Option<&str>,
+    ) -> Option<(Self, String)> {
+        /// Fields of the metadata identifying the package.
+        #[derive(Deserialize)]
+        struct Identity {
+            sha256: String,
+            /// Path of a deb package in the pool of a channel.
+            #[serde(default)]
+            filename: String,
+            /// Path of an rpm package in the repository.
+            #[serde(default)]
+            location: String,
+        }
+
+        let mut cursor = collection
+            .find(doc! { "name": name })
+            .sort(doc! { "created_at": -1 })
+            .await
+            .inspect_err(|e| error!("Unable to retrieve the metadata of {name}: {e}"))
+            .ok()?;
+
+        let path = ver.map(|ver| format!("/{ver}/{name}"));
+
+        while let Some(metadata) = cursor.try_next().await.ok()? {
+            let Ok(identity) = serde_json::from_str::<Identity>(&metadata.metadata) else {
+                continue;
+            };
+
+            let released = path.as_ref().is_none_or(|path| {
+                format!("/{}", identity.filename).ends_with(path)
+                    || identity.location.ends_with(path)
+            });
+
+            if released {
+                return Some((metadata, identity.sha256));
+            }
+        }
+
+        None
+    }
+
+    /// Delete the metadata from the `collection`, so that it's extracted again from the package.
+    pub async fn delete_from(&self, collection: &Collection<PackageMetadata>) -> Result<()> {
+        collection
+            .delete_one(doc! { "name": &self.name, "created_at": self.created_at })
+            .await?;
+
+        Ok(())
+    }
+
+    /// Check if the metadata of the package of type `ty` is of the current version.
+    ///
+    /// Outdated metadata lacks fields added since it was saved.
+    pub fn is_current(&self, ty: &Type) -> bool {
+        match ty {
+            Type::Deb => DebianPackage::is_current(&self.metadata),
+            _ => true,
+        }
     }
 
     pub fn data(self) -> String {
@@ -43,6 +122,16 @@ impl PackageMetadata {
     }
 }
 
+/// Collection of the package metadata of the `project` hosted on the `forge`.
+pub fn metadata_collection(
+    state: &AppState,
+    forge: Forge,
+    project: &str,
+) -> Collection<PackageMetadata> {
+    // Each forge gets its own database, so that projects with the same name don't clash.
+    state.db().database(&forge.to_string()).collection(project)
+}
+
 #[cfg(test)]
 mod tests {
     use std::fs::read;
@@ -54,8 +143,6 @@ mod tests {
         testcontainers::{ContainerAsync, runners::AsyncRunner},
     };
 
-    use crate::apt::DebianPackage;
-
     pub async fn setup_mongodb(container: &ContainerAsync<Mongo>) -> Client {
         let host = container.get_host().await.unwrap();
         let port = container.get_host_port_ipv4(27017).await.unwrap();
@@ -11,7 +11,7 @@ static NAME_RE: LazyLock<Regex> =
 
 // Regex to capture architecture
 static ARCH_RE: LazyLock<Regex> = LazyLock::new(|| {
-    Regex::new(r"(?i)(x86_64|amd64|aarch64|arm64|armhf|armv7|s390x|armv6l|ppc64le|riscv64)")
+    Regex::new(r"(?i)(x86_64|amd64|aarch64|arm64|armhf|armv7|s390x|armv6l|ppc64le|riscv64|i686|i386|noarch|loongarch64|loong64|mips64el)|_(all)\.deb$")
         .unwrap()
 });
 
@@ -21,6 +21,19 @@ static DISTRO_PATTERNS: LazyLock<Vec<(Regex, Dist)>> = LazyLock::new(|| {
         (Regex::new(r"fc(\d+)").unwrap(), Dist::Fedora(None)),
         // Fedora (fedora followed by optional hyphen and digits)
         (Regex::new(r"fedora-?(\d+)?").unwrap(), Dist::Fedora(None)),
+        // RHEL and its rebuilds (el followed by digits)
+        (Regex::new(r"[._-]el(\d+)").unwrap(), Dist::Rhel(None)),
+        (Regex::new(r"rhel-?(\d+)").unwrap(), Dist::Rhel(None)),
+        (Regex::new(r"rocky-?(\d+)").unwrap(), Dist::Rocky(None)),
+        (
+            Regex::new(r"alma(?:linux)?-?(\d+)").unwrap(),
+            Dist::Alma(None),
+        ),
+        (Regex::new(r"centos-?(\d+)").unwrap(), Dist::CentOS(None)),
+        // Amazon Linux (amzn followed by digits)
+        (Regex::new(r"amzn-?(\d+)").unwrap(), Dist::AmazonLinux(None)),
+        // Mageia (mga followed by digits)
+        (Regex::new(r"mga-?(\d+)").unwrap(), Dist::Mageia(None)),
         // openSUSE Leap (lp followed by digits and decimal)
         (Regex::new(r"lp(\d+\.\d+)").unwrap(), Dist::Leap(None)),
         // openSUSE Leap (opensuse-leap followed by optional hyphen and version)
@@ -41,6 +54,13 @@ static DISTRO_PATTERNS: LazyLock<Vec<(Regex, Dist)>> = LazyLock::new(|| {
         (Regex::new(r"ubuntu-?([a-z]+)").unwrap(), Dist::Ubuntu(None)),
         // Ubuntu (without version)
         (Regex::new(r"ubuntu").unwrap(), Dist::Ubuntu(None)),
+        // Alpine (alpine followed by optional hyphen and digits with decimal)
+        (
+            Regex::new(r"alpine-?(\d+\.\d+)").unwrap(),
+            Dist::Alpine(None),
+        ),
+        // Alpine (without version)
+        (Regex::new(r"alpine").unwrap(), Dist::Alpine(None)),
         (Regex::new(r"(?i)suse").unwrap(), Dist::Tumbleweed),
         (Regex::new(r"(tw|tumbleweed)").unwrap(), Dist::Tumbleweed),
     ]
@@ -88,6 +108,7 @@ impl PackageInfo {
         if let Some(caps) = ARCH_RE.captures(filename) {
             architecture = caps
                 .get(1)
+                .or_else(|| caps.get(2))
                 .and_then(|m| m.as_str().to_lowercase().parse().ok());
         }
 
@@ -125,6 +146,8 @@ impl PackageInfo {
 
 #[cfg(test)]
 mod tests {
+    use lenient_semver::parse;
+
     use super::*;
 
     #[test]
@@ -197,6 +220,28 @@ mod tests {
         assert_eq!(info.architecture, Some(Arch::Aarch64));
     }
 
+    #[test]
+    fn test_alpine() {
+        let info = PackageInfo::parse_package("tool-1.2.0-r0-alpine3.20.x86_64.apk");
+        assert_eq!(info.name, Some("tool".into()));
+        assert_eq!(info.distro, Some(Dist::alpine("3.20")));
+        assert_eq!(info.architecture, Some(Arch::Amd64));
+    }
+
+    #[test]
+    fn test_rhel() {
+        let info = PackageInfo::parse_package("tool-1.2.0-1.el9.x86_64.rpm");
+        assert_eq!(info.name, Some("tool".into()));
+        assert_eq!(info.distro, Some(Dist::rhel("9")));
+        assert_eq!(info.architecture, Some(Arch::Amd64));
+
+        let info = PackageInfo::parse_package("tool-1.2.0-1.amzn2023.aarch64.rpm");
+        assert_eq!(info.distro, Some(Dist::AmazonLinux(parse("2023").ok())));
+
+        let info = PackageInfo::parse_package("tool-1.2.0-1.mga9.x86_64.rpm");
+        assert_eq!(info.distro, Some(Dist::Mageia(parse("9").ok())));
+    }
+
     #[test]
     fn test_opensuse_tumbleweed_full_pattern() {
         let filename = "another-package-2.3.4-1.tumbleweed.noarch.rpm";
@@ -249,5 +294,23 @@ mod tests {
 
         let info = PackageInfo::parse_package("fastfetch-linux-riscv64.deb");
         assert_eq!(info.architecture, Some(Arch::RiscV64));
+
+        let info = PackageInfo::parse_package("tool-1.2.0-1.fc41.i686.rpm");
+        assert_eq!(info.architecture, Some(Arch::I386));
+
+        let info = PackageInfo::parse_package("tool-data-1.2.0-1.fc41.noarch.rpm");
+        assert_eq!(info.architecture, Some(Arch::Noarch));
+
+        let info = PackageInfo::parse_package("tool-1.2.0-1.loongarch64.rpm");
+        assert_eq!(info.architecture, Some(Arch::Loong64));
+
+        let info = PackageInfo::parse_package("tool_1.2.0_mips64el.deb");
+        assert_eq!(info.architecture, Some(Arch::Mips64el));
+
+        let info = PackageInfo::parse_package("tool-data_1.2.0_all.deb");
+        assert_eq!(info.architecture, Some(Arch::Noarch));
+
+        let info = PackageInfo::parse_package("install-tool_1.2.0_amd64.deb");
+        assert_eq!(info.architecture, Some(Arch::Amd64));
     }
 }
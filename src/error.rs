@@ -1,17 +1,141 @@
-use axum::http::StatusCode;
+use std::{fmt::Display, time::Duration};
+
+use axum::http::{StatusCode, header::RETRY_AFTER};
 use axum::response::{IntoResponse, Response};
-use tracing::error;
+use tracing::{error, info};
+
+/// Failures with a dedicated response.
+///
+/// They are raised through `anyhow`, either as the error or as its context, and
+/// picked out of the error chain by [`AppError`].
+#[derive(Debug)]
+pub enum PackhubError {
+    /// The repository doesn't exist on the forge.
+    UnknownRepository(String),
+    /// The repository has no release for the requested channel.
+    NoRelease(String),
+    /// A file listed in the index is no longer available on the forge.
+    Gone(String),
+    /// The requested file doesn't exist in the repository.
+    NotFound(String),
+    /// The package manager or its distribution is not supported.
+    UnsupportedClient(String),
+    /// The request has an invalid parameter.
+    InvalidRequest(String),
+    /// The forge rate limits our requests.
+    RateLimited { retry_after: Option<u64> },
+    /// The forge can't be reached or failed to respond.
+    UpstreamUnavailable(String),
+    /// The forge rejected our request.
+    UpstreamRejected(String),
+    /// A package released on the forge couldn't be parsed.
+    ParseFailure(String),
+}
+
+impl PackhubError {
+    fn status(&self) -> StatusCode {
+        match self {
+            PackhubError::UnknownRepository(_)
+            | PackhubError::NoRelease(_)
+            | PackhubError::NotFound(_) => StatusCode::NOT_FOUND,
+            PackhubError::Gone(_) => StatusCode::GONE,
+            PackhubError::UnsupportedClient(_) | PackhubError::InvalidRequest(_) => {
+                StatusCode::BAD_REQUEST
+            }
+            PackhubError::RateLimited { .. } => StatusCode::TOO_MANY_REQUESTS,
+            PackhubError::UpstreamUnavailable(_) => StatusCode::SERVICE_UNAVAILABLE,
+            PackhubError::UpstreamRejected(_) | PackhubError::ParseFailure(_) => {
+                StatusCode::BAD_GATEWAY
+            }
+        }
+    }
+
+    /// How long the client should wait before retrying.
+    fn retry_after(&self) -> Option<Duration> {
+        match self {
+            PackhubError::RateLimited { retry_after } => {
+                Some(Duration::from_secs(retry_after.unwrap_or(60)))
+            }
+            PackhubError::UpstreamUnavailable(_) => Some(Duration::from_secs(60)),
+            _ => None,
+        }
+    }
+}
+
+impl Display for PackhubError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PackhubError::UnknownRepository(repo) => write!(f, "Repository {repo} not found"),
+            PackhubError::NoRelease(msg)
+            | PackhubError::Gone(msg)
+            | PackhubError::NotFound(msg)
+            | PackhubError::UnsupportedClient(msg)
+            | PackhubError::InvalidRequest(msg) => write!(f, "{msg}"),
+            PackhubError::RateLimited { .. } => {
+                write!(f, "Rate limited by the forge, try again later")
+            }
+            PackhubError::UpstreamUnavailable(msg) => write!(f, "Forge unavailable: {msg}"),
+            PackhubError::UpstreamRejected(msg) => {
+                write!(f, "Request rejected by the forge: {msg}")
+            }
+            PackhubError::ParseFailure(package) => {
+                write!(f, "Unable to parse the package {package}")
+            }
+        }
+    }
+}
+
+impl std::error::Error for PackhubError {}
 
 pub struct AppError(anyhow::Error);
 
+impl AppError {
+    /// The outermost [`PackhubError`] of the error chain.
+    fn kind(&self) -> Option<&PackhubError> {
+        // Contexts are only found by downcasting the `anyhow` error itself.
+        self.0
+            .downcast_ref()
+            .or_else(|| self.0.chain().find_map(|e| e.downcast_ref()))
+    }
+}
+
 impl IntoResponse for AppError {
     fn into_response(self) -> Response {
-        error!("Something went wrong: {}", self.0);
-        (
-            StatusCode::NOT_FOUND,
-            format!("Something went wrong: {}", self.0),
-        )
-            .into_response()
+        let unavailable;
+        let kind = match self.kind() {
+            Some(kind) => kind,
+            // Requests to the forges or for the packages which failed to be sent.
+            None if self.0.chain().any(|e| e.is::<reqwest::Error>()) => {
+                unavailable = PackhubError::UpstreamUnavailable(self.0.to_string());
+                &unavailable
+            }
+            None => {
+                error!("Something went wrong: {:#}", self.0);
+                return (
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    format!("Something went wrong: {}", self.0),
+                )
+                    .into_response();
+            }
+        };
+
+        let status = kind.status();
+
+        if status.is_server_error() {
+            error!("{status}: {:#}", self.0);
+        } else {
+            info!("{status}: {:#}", self.0);
+        }
+
+        let mut response = (status, kind.to_string()).into_response();
+
+        if let Some(retry_after) = kind.retry_after() {
+            response
+                .headers_mut()
+                .insert(RETRY_AFTER, retry_after.as_secs().into());
+        }
+
+        response
     }
 }
 
@@ -23,3 +147,38 @@ where
         Self(err.into())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use anyhow::{Context, anyhow};
+
+    use super::*;
+
+    #[test]
+    fn test_error_responses() {
+        let response = |err: anyhow::Error| AppError(err).into_response();
+
+        let res = response(anyhow!(PackhubError::NoRelease(
+            "No release of owner/repo found".to_owned()
+        )));
+        assert_eq!(res.status(), StatusCode::NOT_FOUND);
+
+        // As the context of an error
+        let res = response(
+            Err::<(), _>(anyhow!("invalid gzip header"))
+                .context(PackhubError::ParseFailure("foo.deb".to_owned()))
+                .unwrap_err(),
+        );
+        assert_eq!(res.status(), StatusCode::BAD_GATEWAY);
+
+        let res = response(anyhow!(PackhubError::RateLimited {
+            retry_after: Some(120)
+        }));
+        assert_eq!(res.status(), StatusCode::TOO_MANY_REQUESTS);
+        assert_eq!(res.headers()[RETRY_AFTER], "120");
+
+        let res = response(anyhow!("Something unexpected"));
+        assert_eq!(res.status(), StatusCode::INTERNAL_SERVER_ERROR);
+        assert!(!res.headers().contains_key(RETRY_AFTER));
+    }
+}
@@ -13,19 +13,28 @@ use tracing::{Span, debug};
 
 use crate::state::AppState;
 
+mod apk;
 mod apt;
+mod blob;
+pub mod config;
 mod db;
 mod detect;
 mod error;
 mod package;
+mod pacman;
 pub mod pgp;
 mod platform;
+mod proxy;
 mod repository;
+mod response;
 mod rpm;
 mod script;
 mod selector;
+mod snapshot;
+mod source;
 pub mod state;
 mod utils;
+mod webhook;
 
 static REQWEST: LazyLock<reqwest::Client> = LazyLock::new(|| {
     reqwest::ClientBuilder::new()
@@ -39,6 +48,8 @@ fn v1() -> Router<AppState> {
     Router::new()
         .nest("/apt", apt::apt_routes())
         .nest("/rpm", rpm::rpm_routes_v1())
+        .nest("/pacman", pacman::pacman_routes())
+        .nest("/apk", apk::apk_routes())
         .nest("/keys", pgp::keys())
 }
 
@@ -52,6 +63,7 @@ pub fn app(state: AppState) -> Router {
         .nest("/v1", v1())
         .nest("/v2", v2())
         .nest("/sh", script::script_routes())
+        .nest("/webhooks", webhook::webhook_routes())
         .nest_service("/assets", ServeDir::new("pages/assets"))
         .with_state(state)
         .layer(TraceLayer::new_for_http().on_response(
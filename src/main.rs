@@ -1,14 +1,15 @@
-use std::{env::args, net::SocketAddr};
+use std::{env::args, net::SocketAddr, path::PathBuf};
 
+use anyhow::{Context, Result, bail};
 use axum_server::tls_rustls::RustlsConfig;
-use dotenvy::{dotenv, var};
+use dotenvy::dotenv;
 use tracing::{Level, info};
 use tracing_subscriber::{filter::Targets, prelude::*};
 
-use packhub::{app, state::AppState};
+use packhub::{app, config::Config, state::AppState};
 
 #[tokio::main]
-async fn main() {
+async fn main() -> Result<()> {
     let filter = Targets::new()
         .with_target("tower_http", Level::TRACE)
         .with_target("packhub", Level::TRACE)
@@ -28,41 +29,51 @@ async fn main() {
     }
 
     let mut generate_keys = false;
-
-    if args().len() > 1 {
-        let arg = args().nth(1).unwrap();
-        if arg == "--generate-keys" {
-            generate_keys = true;
+    let mut config_path = None;
+
+    let mut args = args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--generate-keys" => generate_keys = true,
+            "--config" => {
+                config_path = Some(PathBuf::from(
+                    args.next()
+                        .context("--config requires the path of a file")?,
+                ))
+            }
+            _ => bail!("Unknown argument: {arg}"),
         }
     }
 
-    let state = AppState::initialize(generate_keys).await;
+    let config = Config::load(config_path.as_deref()).context("Invalid configuration")?;
+    let server = &config.server;
 
-    let http_addr: SocketAddr = format!("0.0.0.0:{}", var("PACKHUB_HTTP_PORT").unwrap())
-        .parse()
-        .unwrap();
+    let state = AppState::initialize(&config, generate_keys).await?;
 
-    let https_addr: SocketAddr = format!("0.0.0.0:{}", var("PACKHUB_HTTPS_PORT").unwrap())
-        .parse()
-        .unwrap();
+    let http_addr = SocketAddr::new(server.bind_address, server.http_port);
+    let http_server = axum_server::bind(http_addr).serve(app(state.clone()).into_make_service());
 
     info!("listening on {}", http_addr);
-    info!("listening on {}", https_addr);
 
-    let config = RustlsConfig::from_pem_file(
-        var("PACKHUB_CERT_PEM").unwrap(),
-        var("PACKHUB_KEY_PEM").unwrap(),
-    )
-    .await
-    .unwrap();
+    if server.http_only {
+        http_server.await?;
+        return Ok(());
+    }
 
-    let http_server = axum_server::bind(http_addr).serve(app(state.clone()).into_make_service());
+    let https_addr = SocketAddr::new(server.bind_address, server.https_port);
+    info!("listening on {}", https_addr);
+
+    let (Some(cert), Some(key)) = (&server.cert_pem, &server.key_pem) else {
+        bail!("The TLS certificate and key must be set unless the server is HTTP only");
+    };
+    let tls = RustlsConfig::from_pem_file(cert, key)
+        .await
+        .context("Unable to load the TLS certificate")?;
 
     let https_server =
-        axum_server::bind_rustls(https_addr, config).serve(app(state).into_make_service());
+        axum_server::bind_rustls(https_addr, tls).serve(app(state).into_make_service());
 
-    let http = tokio::spawn(async { http_server.await.unwrap() });
-    let https = tokio::spawn(async { https_server.await.unwrap() });
+    tokio::try_join!(http_server, https_server)?;
 
-    _ = tokio::join!(http, https);
+    Ok(())
 }
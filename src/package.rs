@@ -1,7 +1,19 @@
-use std::sync::{Arc, Mutex};
+use std::{
+    fmt::Display,
+    fs::File,
+    io::{BufReader, Read},
+    sync::{Arc, Mutex, OnceLock},
+};
 
 use anyhow::{Result, bail};
 use chrono::{DateTime, Utc};
+use futures_util::StreamExt;
+use md5::Md5;
+use sha1::{Digest, Sha1};
+use sha2::{Sha256, Sha512};
+use tempfile::NamedTempFile;
+use tokio::io::AsyncWriteExt;
+use tracing::warn;
 
 use crate::{
     REQWEST,
@@ -12,10 +24,15 @@ use crate::{
 struct InnerPackage {
     tipe: Type,
     info: PackageInfo,
+    /// Name of the release asset, which isn't always the last segment of its URL.
+    file_name: String,
     url: String,
     ver: String,
     data: Mutex<Data>,
     created: DateTime<Utc>,
+    /// URL of the detached signature (`.sig`) released along with the package.
+    signature_url: OnceLock<String>,
+    signature: Mutex<Option<Vec<u8>>>,
 }
 
 #[derive(Clone, PartialEq)]
@@ -28,18 +45,188 @@ pub struct Package {
 /// It is used to differentiate between package data and metadata.
 ///
 /// `Data::Package` is used for package data. It is the actual package
-/// file which needs to be processed to extract the metadata.
+/// file, downloaded to disk, which needs to be processed to extract the metadata.
 ///
 /// `Data::Metadata` is used for package metadata.
 ///
 /// `Data::None` is used when no data is available.
 #[derive(Clone, PartialEq)]
 pub enum Data {
-    Package(Vec<u8>),
+    Package(PackageFile),
     Metadata(String),
     None,
 }
 
+/// A package file downloaded to a temporary file, which is removed once it's dropped.
+///
+/// The file is hashed while it's downloaded, so it only has to be read for its metadata.
+#[derive(Clone)]
+pub struct PackageFile {
+    inner: Arc<InnerPackageFile>,
+}
+
+struct InnerPackageFile {
+    file: NamedTempFile,
+    size: u64,
+    md5: String,
+    sha1: String,
+    sha256: String,
+    sha512: String,
+}
+
+/// Digests of a file computed as it's written.
+#[derive(Default)]
+struct Hashes {
+    md5: Md5,
+    sha1: Sha1,
+    sha256: Sha256,
+    sha512: Sha512,
+}
+
+/// The package is larger than the maximum size of the downloaded packages.
+#[derive(Debug)]
+pub struct AssetTooLarge {
+    url: String,
+    max_size: u64,
+}
+
+impl Display for AssetTooLarge {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "The package {} is larger than {} bytes",
+            self.url, self.max_size
+        )
+    }
+}
+
+impl std::error::Error for AssetTooLarge {}
+
+impl Hashes {
+    fn update(&mut self, data: &[u8]) {
+        self.md5.update(data);
+        self.sha1.update(data);
+        self.sha256.update(data);
+        self.sha512.update(data);
+    }
+
+    fn finish(self, file: NamedTempFile, size: u64) -> PackageFile {
+        PackageFile {
+            inner: Arc::new(InnerPackageFile {
+                file,
+                size,
+                md5: format!("{:x}", self.md5.finalize()),
+                sha1: format!("{:x}", self.sha1.finalize()),
+                sha256: format!("{:x}", self.sha256.finalize()),
+                sha512: format!("{:x}", self.sha512.finalize()),
+            }),
+        }
+    }
+}
+
+impl PackageFile {
+    /// Download the file at the `url` to a temporary file.
+    ///
+    /// Files larger than `max_size` bytes fail with [`AssetTooLarge`], without being
+    /// downloaded completely.
+    pub async fn download(url: &str, max_size: u64) -> Result<PackageFile> {
+        let too_large = || AssetTooLarge {
+            url: url.to_owned(),
+            max_size,
+        };
+
+        let res = REQWEST.get(url).send().await?.error_for_status()?;
+
+        if res.content_length().is_some_and(|size| size > max_size) {
+            bail!(too_large());
+        }
+
+        let file = temp_file()?;
+        let mut writer = tokio::fs::File::from_std(file.reopen()?);
+        let mut hashes = Hashes::default();
+        let mut size = 0;
+
+        let mut stream = res.bytes_stream();
+
+        while let Some(chunk) = stream.next().await {
+            let chunk = chunk?;
+
+            size += chunk.len() as u64;
+            if size > max_size {
+                bail!(too_large());
+            }
+
+            hashes.update(&chunk);
+            writer.write_all(&chunk).await?;
+        }
+
+        writer.flush().await?;
+
+        Ok(hashes.finish(file, size))
+    }
+
+    #[cfg(test)]
+    /// Write the `data` to a temporary file.
+    pub fn from_bytes(data: &[u8]) -> PackageFile {
+        use std::io::Write;
+
+        let mut file = temp_file().unwrap();
+        file.write_all(data).unwrap();
+
+        let mut hashes = Hashes::default();
+        hashes.update(data);
+
+        hashes.finish(file, data.len() as u64)
+    }
+
+    /// Open the file for reading from its start.
+    pub fn open(&self) -> Result<BufReader<File>> {
+        Ok(BufReader::new(self.inner.file.reopen()?))
+    }
+
+    pub fn size(&self) -> u64 {
+        self.inner.size
+    }
+
+    pub fn md5(&self) -> &str {
+        &self.inner.md5
+    }
+
+    pub fn sha1(&self) -> &str {
+        &self.inner.sha1
+    }
+
+    pub fn sha256(&self) -> &str {
+        &self.inner.sha256
+    }
+
+    pub fn sha512(&self) -> &str {
+        &self.inner.sha512
+    }
+}
+
+impl std::fmt::Debug for PackageFile {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} ({} bytes)",
+            self.inner.file.path().display(),
+            self.size()
+        )
+    }
+}
+
+impl PartialEq for PackageFile {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.inner, &other.inner)
+    }
+}
+
+/// Create a temporary file for a package, in the directory set by `TMPDIR`.
+fn temp_file() -> Result<NamedTempFile> {
+    Ok(tempfile::Builder::new().prefix("packhub-").tempfile()?)
+}
+
 impl std::fmt::Debug for Package {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(f, "{}", self.file_name())
@@ -64,10 +251,12 @@ impl PartialEq for InnerPackage {
     fn eq(&self, other: &Self) -> bool {
         self.tipe == other.tipe
             && self.info == other.info
+            && self.file_name == other.file_name
             && self.url == other.url
             && self.ver == other.ver
             && *self.data.lock().unwrap() == *other.data.lock().unwrap()
             && self.created == other.created
+            && self.signature_url == other.signature_url
     }
 }
 
@@ -89,10 +278,13 @@ impl Package {
         let inner = InnerPackage {
             tipe,
             info,
+            file_name: name.to_owned(),
             url,
             ver,
             data: Mutex::new(Data::None),
             created,
+            signature_url: OnceLock::new(),
+            signature: Mutex::new(None),
         };
 
         Ok(Self {
@@ -135,20 +327,34 @@ impl Package {
     }
 
     pub fn file_name(&self) -> &str {
-        self.inner.url.split('/').last().unwrap()
+        &self.inner.file_name
     }
 
     /// Download package data
     ///
     /// It is required to call this function before calling the `data()` function.
-    pub async fn download(&self) -> Result<()> {
-        let data = REQWEST
-            .get(self.download_url())
-            .send()
-            .await?
-            .bytes()
-            .await?;
-        *self.inner.data.lock().unwrap() = Data::Package(data.to_vec());
+    /// Packages larger than `max_size` bytes fail with [`AssetTooLarge`].
+    pub async fn download(&self, max_size: u64) -> Result<()> {
+        let file = PackageFile::download(self.download_url(), max_size).await?;
+        *self.inner.data.lock().unwrap() = Data::Package(file);
+
+        // Without its signature, the package is served unsigned rather than failing the index.
+        if let Some(url) = self.signature_url() {
+            let signature = PackageFile::download(url, max_size).await.and_then(|file| {
+                let mut signature = Vec::new();
+                file.open()?.read_to_end(&mut signature)?;
+                Ok(signature)
+            });
+
+            match signature {
+                Ok(signature) => *self.inner.signature.lock().unwrap() = Some(signature),
+                Err(e) => warn!(
+                    "Unable to download the signature of the package {:?}: {e}",
+                    self.file_name()
+                ),
+            }
+        }
+
         Ok(())
     }
 
@@ -165,7 +371,7 @@ impl Package {
     ///
     /// It's for testing purpose.
     pub fn set_package_data(&self, data: Vec<u8>) {
-        *self.inner.data.lock().unwrap() = Data::Package(data);
+        *self.inner.data.lock().unwrap() = Data::Package(PackageFile::from_bytes(&data));
     }
 
     pub fn creation_date(&self) -> &DateTime<Utc> {
@@ -177,6 +383,31 @@ impl Package {
         *self.inner.data.lock().unwrap() = Data::Metadata(metadata);
     }
 
+    /// Set the URL of the detached signature of the package.
+    pub fn set_signature_url(&self, url: String) {
+        _ = self.inner.signature_url.set(url);
+    }
+
+    /// URL of the detached signature of the package, if it was released with one.
+    pub fn signature_url(&self) -> Option<&str> {
+        self.inner.signature_url.get().map(String::as_str)
+    }
+
+    /// Return the detached signature of the package.
+    ///
+    /// It is only available after the `download()` function, when the package has a signature.
+    pub fn signature(&self) -> Option<Vec<u8>> {
+        self.inner.signature.lock().unwrap().clone()
+    }
+
+    #[cfg(test)]
+    /// Set the detached signature of the package.
+    ///
+    /// It's for testing purpose.
+    pub fn set_signature(&self, signature: Vec<u8>) {
+        *self.inner.signature.lock().unwrap() = Some(signature);
+    }
+
     /// Check if metadata is available.
     pub fn is_metadata_available(&self) -> bool {
         matches!(*self.inner.data.lock().unwrap(), Data::Metadata(_))
@@ -184,6 +415,14 @@ impl Package {
 }
 
 fn split_extention(s: &str) -> Option<Type> {
+    // Pacman packages have a compound extension, like `.pkg.tar.zst`.
+    if let Some((name, compression)) = s.rsplit_once(".pkg.tar")
+        && !name.is_empty()
+        && matches!(compression, "" | ".zst" | ".xz" | ".gz" | ".bz2")
+    {
+        return Some(Type::Pacman);
+    }
+
     let mut str = String::with_capacity(3);
     let mut index = 0;
 
@@ -204,6 +443,7 @@ fn split_extention(s: &str) -> Option<Type> {
     let tipe = match str.as_str() {
         "bed" => Type::Deb,
         "mpr" => Type::Rpm,
+        "kpa" => Type::Apk,
         _ => return None,
     };
 
@@ -277,6 +517,92 @@ pub(crate) mod tests {
         assert_eq!(*pack.ty(), Type::Rpm);
     }
 
+    #[tokio::test]
+    async fn test_download() {
+        use axum::{Router, body::Body, routing::get};
+        use axum_test::TestServer;
+        use futures_util::stream;
+
+        use crate::utils::hashsum;
+
+        let data = vec![7u8; 64 * 1024];
+        let body = data.clone();
+        let chunks = data.chunks(4096).map(<[u8]>::to_vec).collect::<Vec<_>>();
+
+        let app = Router::new()
+            .route("/package.deb", get(move || async move { body }))
+            // Without a `Content-Length`, so the size is only known while downloading.
+            .route(
+                "/chunked.deb",
+                get(move || async move {
+                    Body::from_stream(stream::iter(
+                        chunks.into_iter().map(Ok::<_, std::io::Error>),
+                    ))
+                }),
+            );
+        let server = TestServer::builder().http_transport().build(app).unwrap();
+
+        let url = server.server_url("/package.deb").unwrap();
+        let file = PackageFile::download(url.as_str(), 1024 * 1024)
+            .await
+            .unwrap();
+        assert_eq!(file.size(), data.len() as u64);
+        assert_eq!(file.md5(), hashsum::<Md5>(&data));
+        assert_eq!(file.sha1(), hashsum::<Sha1>(&data));
+        assert_eq!(file.sha256(), hashsum::<Sha256>(&data));
+        assert_eq!(file.sha512(), hashsum::<Sha512>(&data));
+
+        let mut downloaded = Vec::new();
+        std::io::Read::read_to_end(&mut file.open().unwrap(), &mut downloaded).unwrap();
+        assert_eq!(downloaded, data);
+
+        // Larger than the maximum size
+        for path in ["/package.deb", "/chunked.deb"] {
+            let url = server.server_url(path).unwrap();
+            let err = PackageFile::download(url.as_str(), 1024).await.unwrap_err();
+            assert!(err.is::<AssetTooLarge>(), "{path}: {err}");
+        }
+
+        let url = server.server_url("/chunked.deb").unwrap();
+        let file = PackageFile::download(url.as_str(), 1024 * 1024)
+            .await
+            .unwrap();
+        assert_eq!(file.sha256(), hashsum::<Sha256>(&data));
+    }
+
+    #[tokio::test]
+    async fn test_download_signature() {
+        use axum::{Router, routing::get};
+        use axum_test::TestServer;
+
+        let app = Router::new()
+            .route("/tool.pkg.tar.zst", get(|| async { vec![7u8; 1024] }))
+            .route("/tool.pkg.tar.zst.sig", get(|| async { vec![1u8; 64] }))
+            .route("/large.pkg.tar.zst.sig", get(|| async { vec![1u8; 2048] }));
+        let server = TestServer::builder().http_transport().build(app).unwrap();
+
+        let download = async |signature: &str| {
+            let url = server.server_url("/tool.pkg.tar.zst").unwrap();
+            let package = Package::detect_package(
+                "tool.pkg.tar.zst",
+                "1.0.0".to_owned(),
+                url.to_string(),
+                DateTime::UNIX_EPOCH,
+            )
+            .unwrap();
+            package.set_signature_url(server.server_url(signature).unwrap().to_string());
+
+            package.download(1024).await.unwrap();
+            package.signature()
+        };
+
+        assert_eq!(download("/tool.pkg.tar.zst.sig").await, Some(vec![1u8; 64]));
+
+        // Served unsigned when the signature is missing or too large
+        assert_eq!(download("/missing.pkg.tar.zst.sig").await, None);
+        assert_eq!(download("/large.pkg.tar.zst.sig").await, None);
+    }
+
     #[test]
     fn test_package_change_propagation() {
         let pack = Package::detect_package(
@@ -305,6 +631,15 @@ pub(crate) mod tests {
             split_extention("OpenBangla-Keyboard_2.0.0-fedora36.rpm"),
             Some(Type::Rpm)
         );
+        assert_eq!(
+            split_extention("fastfetch-2.40.4-1-x86_64.pkg.tar.zst"),
+            Some(Type::Pacman)
+        );
+        assert_eq!(
+            split_extention("fastfetch-2.40.4-1-x86_64.pkg.tar.zst.sig"),
+            None
+        );
+        assert_eq!(split_extention("tool-1.2.0-r0.apk"), Some(Type::Apk));
         assert_eq!(split_extention("caprine_2.56.1_amd64.snap"), None);
         assert_eq!(split_extention("deb"), None);
     }
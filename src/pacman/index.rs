@@ -0,0 +1,265 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use askama::Template;
+
+use crate::{pacman::package::PacmanPackage, utils::gzip_compression};
+
+#[derive(Template)]
+#[template(path = "desc")]
+struct Desc {
+    sections: Vec<(&'static str, Vec<String>)>,
+}
+
+impl Desc {
+    /// Sections of the `desc` file in the order `repo-add` writes them. Empty sections are left out.
+    fn new(package: &PacmanPackage) -> Self {
+        let sections = vec![
+            ("FILENAME", vec![package.filename.clone()]),
+            ("NAME", package.fields("pkgname")),
+            ("BASE", package.fields("pkgbase")),
+            ("VERSION", package.fields("pkgver")),
+            ("DESC", package.fields("pkgdesc")),
+            ("GROUPS", package.fields("group")),
+            ("CSIZE", vec![package.csize.to_string()]),
+            ("ISIZE", package.fields("size")),
+            ("MD5SUM", vec![package.md5.clone()]),
+            ("SHA256SUM", vec![package.sha256.clone()]),
+            ("PGPSIG", package.pgpsig.iter().cloned().collect()),
+            ("URL", package.fields("url")),
+            ("LICENSE", package.fields("license")),
+            ("ARCH", package.fields("arch")),
+            ("BUILDDATE", package.fields("builddate")),
+            ("PACKAGER", package.fields("packager")),
+            ("REPLACES", package.fields("replaces")),
+            ("CONFLICTS", package.fields("conflict")),
+            ("PROVIDES", package.fields("provides")),
+            ("DEPENDS", package.fields("depend")),
+            ("OPTDEPENDS", package.fields("optdepend")),
+            ("MAKEDEPENDS", package.fields("makedepend")),
+            ("CHECKDEPENDS", package.fields("checkdepend")),
+        ];
+
+        let sections = sections
+            .into_iter()
+            .filter(|(_, values)| !values.is_empty())
+            .collect();
+
+        Self { sections }
+    }
+}
+
+/// Render the package (`db`) and files (`files`) databases of each architecture.
+///
+/// The files are named `{arch}/db` and `{arch}/files`. Packages built for `any` architecture
+/// are in the databases of every architecture, and in the `any/` databases which are served
+/// to the architectures without a package of their own.
+pub fn get_databases(packages: &[PacmanPackage]) -> Vec<(String, Vec<u8>)> {
+    // A database holds one version of a package, so keep the latest built one.
+    let mut latest: BTreeMap<(&str, &str), &PacmanPackage> = BTreeMap::new();
+
+    for package in packages {
+        let key = (package.arch(), package.name());
+        let build_date =
+            |p: &PacmanPackage| p.field("builddate").and_then(|d| d.parse::<u64>().ok());
+
+        if latest
+            .get(&key)
+            .is_none_or(|current| build_date(current) < build_date(package))
+        {
+            latest.insert(key, package);
+        }
+    }
+
+    let mut arches: BTreeSet<&str> = latest.keys().map(|(arch, _)| *arch).collect();
+    arches.insert("any");
+
+    let mut files = Vec::new();
+
+    for arch in arches {
+        let packages: Vec<_> = latest
+            .iter()
+            .filter(|((package_arch, _), _)| *package_arch == arch || *package_arch == "any")
+            .map(|(_, package)| *package)
+            .collect();
+
+        files.push((format!("{arch}/db"), database(&packages, false)));
+        files.push((format!("{arch}/files"), database(&packages, true)));
+    }
+
+    files
+}
+
+/// Create the gzip compressed tar database of the packages.
+///
+/// Each package has a `{name}-{version}/` directory with its `desc` file,
+/// and its `files` file in the files database.
+fn database(packages: &[&PacmanPackage], with_files: bool) -> Vec<u8> {
+    let mut archive = tar::Builder::new(Vec::new());
+
+    for package in packages {
+        let dir = format!("{}-{}", package.name(), package.version());
+        append(&mut archive, &format!("{dir}/"), None);
+
+        let desc = Desc::new(package).render().unwrap();
+        append(&mut archive, &format!("{dir}/desc"), Some(desc.as_bytes()));
+
+        if with_files {
+            let mut files = String::from("%FILES%\n");
+            for file in &package.files {
+                files.push_str(file);
+                files.push('\n');
+            }
+
+            append(
+                &mut archive,
+                &format!("{dir}/files"),
+                Some(files.as_bytes()),
+            );
+        }
+    }
+
+    gzip_compression(&archive.into_inner().unwrap())
+}
+
+/// Append a file, or a directory when there is no `data`.
+///
+/// The modification time is left unset, so that the database is reproducible.
+fn append(archive: &mut tar::Builder<Vec<u8>>, path: &str, data: Option<&[u8]>) {
+    let mut header = tar::Header::new_gnu();
+
+    match data {
+        Some(data) => {
+            header.set_mode(0o644);
+            header.set_size(data.len() as u64);
+        }
+        None => {
+            header.set_entry_type(tar::EntryType::Directory);
+            header.set_mode(0o755);
+            header.set_size(0);
+        }
+    }
+
+    archive
+        .append_data(&mut header, path, data.unwrap_or_default())
+        .unwrap();
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Read;
+
+    use insta::assert_snapshot;
+
+    use super::*;
+    use crate::{
+        package::tests::package,
+        pacman::package::tests::{PKGINFO, pkg},
+        utils::decoder,
+    };
+
+    fn pacman_package(file: &str, pkginfo: &str) -> PacmanPackage {
+        let package = package(file);
+        package.set_package_data(pkg(pkginfo));
+
+        PacmanPackage::from_package(&package).unwrap()
+    }
+
+    /// Entries of the database and the content of its files.
+    fn entries(database: &[u8]) -> Vec<(String, String)> {
+        let mut archive = tar::Archive::new(decoder(".gz", database).unwrap());
+
+        archive
+            .entries()
+            .unwrap()
+            .map(|entry| {
+                let mut entry = entry.unwrap();
+                let path = entry.path().unwrap().to_string_lossy().into_owned();
+                let mut content = String::new();
+                entry.read_to_string(&mut content).unwrap();
+
+                (path, content)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_desc() {
+        let mut package = pacman_package("tool-1.2.0-1-x86_64.pkg.tar.zst", PKGINFO);
+        package.csize = 2048;
+        package.md5 = "d41d8cd98f00b204e9800998ecf8427e".to_owned();
+        package.sha256 =
+            "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855".to_owned();
+        package.pgpsig = Some("c2lnbmF0dXJl".to_owned());
+
+        assert_snapshot!(Desc::new(&package).render().unwrap());
+    }
+
+    #[test]
+    fn test_databases() {
+        let x86_64 = pacman_package("tool-1.2.0-1-x86_64.pkg.tar.zst", PKGINFO);
+        let older = pacman_package(
+            "tool-1.1.0-1-x86_64.pkg.tar.zst",
+            &PKGINFO
+                .replace("1.2.0-1", "1.1.0-1")
+                .replace("1719792000", "1719700000"),
+        );
+        let aarch64 = pacman_package(
+            "tool-1.2.0-1-aarch64.pkg.tar.zst",
+            &PKGINFO.replace("x86_64", "aarch64"),
+        );
+        let any = pacman_package(
+            "tool-data-1.2.0-1-any.pkg.tar.zst",
+            &PKGINFO
+                .replace("pkgname = tool", "pkgname = tool-data")
+                .replace("x86_64", "any"),
+        );
+
+        let databases = get_databases(&[older, x86_64, aarch64, any]);
+        let names: Vec<_> = databases.iter().map(|(name, _)| name.as_str()).collect();
+        assert_eq!(
+            names,
+            [
+                "aarch64/db",
+                "aarch64/files",
+                "any/db",
+                "any/files",
+                "x86_64/db",
+                "x86_64/files"
+            ]
+        );
+
+        let entries = |name| {
+            let (_, database) = databases.iter().find(|(n, _)| n == name).unwrap();
+            entries(database)
+        };
+
+        let x86_64_db = entries("x86_64/db");
+        let paths: Vec<_> = x86_64_db.iter().map(|(path, _)| path.as_str()).collect();
+        assert_eq!(
+            paths,
+            [
+                "tool-data-1.2.0-1/",
+                "tool-data-1.2.0-1/desc",
+                "tool-1.2.0-1/",
+                "tool-1.2.0-1/desc"
+            ]
+        );
+        assert!(
+            x86_64_db[3]
+                .1
+                .contains("%FILENAME%\ntool-1.2.0-1-x86_64.pkg.tar.zst\n")
+        );
+
+        let any_files = entries("any/files");
+        assert_eq!(any_files.len(), 3);
+        assert_eq!(any_files[2].0, "tool-data-1.2.0-1/files");
+        assert_eq!(any_files[2].1, "%FILES%\nusr/\nusr/bin/\nusr/bin/tool\n");
+
+        // Rendering is reproducible
+        let x86_64 = pacman_package("tool-1.2.0-1-x86_64.pkg.tar.zst", PKGINFO);
+        assert_eq!(
+            get_databases(&[x86_64]),
+            get_databases(&[pacman_package("tool-1.2.0-1-x86_64.pkg.tar.zst", PKGINFO)])
+        );
+    }
+}
@@ -0,0 +1,5 @@
+mod index;
+mod package;
+mod routes;
+
+pub use self::{package::PacmanPackage, routes::pacman_routes};
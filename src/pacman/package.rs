@@ -0,0 +1,195 @@
+use std::io::Read;
+
+use anyhow::{Context, Result, bail};
+use base64::{Engine, prelude::BASE64_STANDARD};
+use serde::{Deserialize, Serialize};
+use serde_json::{from_str, to_string};
+
+use crate::{
+    package::{Data, Package},
+    utils::{PkgInfo, decoder, parse_pkginfo},
+};
+
+/// Pacman package (.pkg.tar.zst)
+#[derive(Serialize, Deserialize, Debug)]
+pub struct PacmanPackage {
+    pub info: PkgInfo,
+    pub filename: String,
+    pub csize: usize,
+    pub md5: String,
+    pub sha256: String,
+    /// Base64 encoded detached signature released along with the package.
+    pub pgpsig: Option<String>,
+    /// Files installed by the package. Directories have a trailing `/`.
+    pub files: Vec<String>,
+}
+
+impl PacmanPackage {
+    /// Create a new Pacman package from a package.
+    ///
+    /// Also sets metadata of the package.
+    pub fn from_package(package: &Package) -> Result<Self> {
+        // Create the pacman package from the metadata if it is present.
+        if let Data::Metadata(metadata) = package.data() {
+            let package: PacmanPackage = from_str(&metadata)?;
+
+            return Ok(package);
+        }
+
+        let Data::Package(file) = package.data() else {
+            bail!("Package data is not available");
+        };
+
+        let filename = package.file_name().to_owned();
+        let (_, compression) = filename
+            .rsplit_once(".pkg.tar")
+            .context("Not a pacman package")?;
+
+        let pgpsig = package
+            .signature()
+            .map(|signature| BASE64_STANDARD.encode(signature));
+
+        let (info, files) = read_archive(decoder(compression, file.open()?)?)
+            .context("Error occurred while reading the .PKGINFO file from package")?;
+
+        let pkg = Self {
+            info,
+            filename,
+            csize: file.size() as usize,
+            md5: file.md5().to_owned(),
+            sha256: file.sha256().to_owned(),
+            pgpsig,
+            files,
+        };
+
+        let metadata = to_string(&pkg)?;
+        package.set_metadata(metadata);
+
+        Ok(pkg)
+    }
+
+    /// First value of the `.PKGINFO` field.
+    pub fn field(&self, key: &str) -> Option<&str> {
+        self.info.get(key)?.first().map(String::as_str)
+    }
+
+    /// Values of the `.PKGINFO` field.
+    pub fn fields(&self, key: &str) -> Vec<String> {
+        self.info.get(key).cloned().unwrap_or_default()
+    }
+
+    pub fn name(&self) -> &str {
+        self.field("pkgname").unwrap_or_default()
+    }
+
+    pub fn version(&self) -> &str {
+        self.field("pkgver").unwrap_or_default()
+    }
+
+    /// Architecture of the package (`x86_64`, `aarch64`) or `any`.
+    pub fn arch(&self) -> &str {
+        self.field("arch").unwrap_or("any")
+    }
+}
+
+/// Read the `.PKGINFO` file and the list of installed files from the package archive.
+fn read_archive(data: impl Read) -> Result<(PkgInfo, Vec<String>)> {
+    let mut info = None;
+    let mut files = Vec::new();
+    let mut archive = tar::Archive::new(data);
+
+    for entry in archive.entries()? {
+        let mut entry = entry?;
+        let path = entry.path()?.to_string_lossy().into_owned();
+        let path = path.trim_start_matches("./").trim_end_matches('/');
+
+        if path == ".PKGINFO" {
+            let mut content = String::new();
+            entry.read_to_string(&mut content)?;
+            info = Some(parse_pkginfo(&content));
+        } else if path.is_empty() || (path.starts_with('.') && !path.contains('/')) {
+            // Metadata files of pacman (`.MTREE`, `.BUILDINFO`, `.INSTALL`)
+        } else if entry.header().entry_type().is_dir() {
+            files.push(format!("{path}/"));
+        } else {
+            files.push(path.to_owned());
+        }
+    }
+
+    files.sort();
+
+    Ok((info.context(".PKGINFO file not found")?, files))
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::package::tests::package;
+
+    pub(crate) const PKGINFO: &str = "# Generated by makepkg 7.0.0
+pkgname = tool
+pkgbase = tool
+pkgver = 1.2.0-1
+pkgdesc = A tool = does things
+url = https://example.com
+builddate = 1719792000
+packager = Unknown Packager
+size = 1024
+arch = x86_64
+license = MIT
+depend = glibc
+depend = gcc-libs>=13
+optdepend = git: for the git integration
+";
+
+    /// Build a package with the `.PKGINFO` file.
+    pub(crate) fn pkg(pkginfo: &str) -> Vec<u8> {
+        let mut archive = tar::Builder::new(Vec::new());
+        for (path, kind, data) in [
+            (".PKGINFO", tar::EntryType::Regular, pkginfo.as_bytes()),
+            (".MTREE", tar::EntryType::Regular, &[][..]),
+            ("usr/", tar::EntryType::Directory, &[][..]),
+            ("usr/bin/", tar::EntryType::Directory, &[][..]),
+            ("usr/bin/tool", tar::EntryType::Regular, &[][..]),
+        ] {
+            let mut header = tar::Header::new_gnu();
+            header.set_entry_type(kind);
+            header.set_size(data.len() as u64);
+            archive.append_data(&mut header, path, data).unwrap();
+        }
+
+        zstd::encode_all(&archive.into_inner().unwrap()[..], 0).unwrap()
+    }
+
+    #[test]
+    fn test_parsing() {
+        let package = package("tool-1.2.0-1-x86_64.pkg.tar.zst");
+        let data = pkg(PKGINFO);
+        package.set_package_data(data.clone());
+        package.set_signature(b"signature".to_vec());
+
+        let pkg = PacmanPackage::from_package(&package).unwrap();
+        assert_eq!(pkg.name(), "tool");
+        assert_eq!(pkg.version(), "1.2.0-1");
+        assert_eq!(pkg.arch(), "x86_64");
+        assert_eq!(pkg.field("pkgdesc"), Some("A tool = does things"));
+        assert_eq!(pkg.fields("depend"), ["glibc", "gcc-libs>=13"]);
+        assert_eq!(pkg.files, ["usr/", "usr/bin/", "usr/bin/tool"]);
+        assert_eq!(pkg.filename, "tool-1.2.0-1-x86_64.pkg.tar.zst");
+        assert_eq!(pkg.csize, data.len());
+        assert_eq!(pkg.pgpsig.as_deref(), Some("c2lnbmF0dXJl"));
+
+        // The package data should have been replaced by the metadata
+        assert!(matches!(package.data(), Data::Metadata(_)));
+        let pkg = PacmanPackage::from_package(&package).unwrap();
+        assert_eq!(pkg.name(), "tool");
+    }
+
+    #[test]
+    fn test_without_pkginfo() {
+        let package = package("tool-1.2.0-1-x86_64.pkg.tar");
+        package.set_package_data(tar::Builder::new(Vec::new()).into_inner().unwrap());
+
+        assert!(PacmanPackage::from_package(&package).is_err());
+    }
+}
@@ -0,0 +1,172 @@
+use anyhow::Context;
+use axum::{
+    Router,
+    extract::{Path, State},
+    http::{HeaderMap, Method},
+    response::Response,
+    routing::get,
+};
+
+use chrono::{DateTime, Utc};
+
+use crate::{
+    blob::stored_package,
+    error::{AppError, PackhubError},
+    pacman::{index::get_databases, package::PacmanPackage},
+    proxy::proxy_package,
+    repository::{Repository, channel_releases},
+    response::data_response,
+    source::Forge,
+    state::AppState,
+    utils::{Dist, ReleaseChannel},
+};
+
+/// Name of the database snapshot file for the requested `file`.
+///
+/// Pacman requests the databases as `{repo}.db` and `{repo}.files` (symlinks to the
+/// `.tar.gz` archives made by `repo-add`), along with their `.sig` signatures.
+fn database_file(file: &str) -> Option<String> {
+    let (file, sig) = match file.strip_suffix(".sig") {
+        Some(file) => (file, ".sig"),
+        None => (file, ""),
+    };
+    let file = file.strip_suffix(".tar.gz").unwrap_or(file);
+
+    match file.rsplit_once('.') {
+        Some((_, kind @ ("db" | "files"))) => Some(format!("{kind}{sig}")),
+        _ => None,
+    }
+}
+
+async fn handle_database(
+    state: &AppState,
+    forge: Forge,
+    owner: &str,
+    repo: &str,
+    channel: &ReleaseChannel,
+    arch: &str,
+    file: &str,
+) -> Result<(Vec<u8>, Option<DateTime<Utc>>), AppError> {
+    let mut repo = Repository::from_forge(forge, owner, repo, channel, state).await?;
+
+    let snapshot = repo
+        .snapshot(Dist::Arch, |packages| {
+            let packages: Vec<PacmanPackage> = packages
+                .iter()
+                .map(|p| {
+                    PacmanPackage::from_package(p)
+                        .with_context(|| PackhubError::ParseFailure(p.file_name().to_owned()))
+                })
+                .collect::<Result<Vec<_>, _>>()?;
+
+            let mut files = Vec::new();
+
+            for (name, database) in get_databases(&packages) {
+                files.push((format!("{name}.sig"), state.detached_sign_data(&database)?));
+                files.push((name, database));
+            }
+
+            Ok(files)
+        })
+        .await?;
+
+    let database = snapshot
+        .file(&format!("{arch}/{file}"))
+        .or_else(|| snapshot.file(&format!("any/{file}")))
+        .with_context(|| PackhubError::NotFound(format!("Unknown file requested: {file}")))?;
+
+    Ok((database.to_owned(), snapshot.last_modified()))
+}
+
+/// Proxy a package or its signature.
+///
+/// Pacman only requests the file name, so it is searched among the assets of the releases.
+async fn handle_package(
+    state: &AppState,
+    forge: Forge,
+    owner: &str,
+    repo: &str,
+    channel: &ReleaseChannel,
+    file: &str,
+    (method, headers): (&Method, &HeaderMap),
+) -> Result<Response, AppError> {
+    // The file names of pacman packages contain their version.
+    if let Some(res) = stored_package(state, (forge, owner, repo), None, file, headers).await {
+        return Ok(res);
+    }
+
+    let releases = channel_releases(forge, owner, repo, channel, state).await?;
+    let url = releases
+        .iter()
+        .flat_map(|release| &release.assets)
+        .find(|asset| asset.name == file)
+        .map(|asset| asset.url.clone())
+        .with_context(|| PackhubError::NotFound(format!("Unknown file requested: {file}")))?;
+
+    proxy_package(
+        state,
+        (forge, owner, repo),
+        None,
+        file,
+        &url,
+        (method, headers),
+    )
+    .await
+}
+
+#[tracing::instrument(name = "Pacman", skip_all, fields(file = file))]
+async fn pacman_file(
+    State(state): State<AppState>,
+    Path((forge, owner, repo, channel, arch, file)): Path<(
+        Forge,
+        String,
+        String,
+        ReleaseChannel,
+        String,
+        String,
+    )>,
+    method: Method,
+    headers: HeaderMap,
+) -> Result<Response, AppError> {
+    match database_file(&file) {
+        Some(database) => {
+            let (data, last_modified) =
+                handle_database(&state, forge, &owner, &repo, &channel, &arch, &database).await?;
+
+            Ok(data_response(data, last_modified, &headers))
+        }
+        None => {
+            let request = (&method, &headers);
+            handle_package(&state, forge, &owner, &repo, &channel, &file, request).await
+        }
+    }
+}
+
+pub fn pacman_routes() -> Router<AppState> {
+    Router::new().route(
+        "/{forge}/{owner}/{repo}/{channel}/{arch}/{file}",
+        get(pacman_file),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_database_file() {
+        assert_eq!(database_file("tool.db").as_deref(), Some("db"));
+        assert_eq!(database_file("tool.db.tar.gz").as_deref(), Some("db"));
+        assert_eq!(database_file("tool.db.sig").as_deref(), Some("db.sig"));
+        assert_eq!(
+            database_file("tool-nightly.files").as_deref(),
+            Some("files")
+        );
+        assert_eq!(
+            database_file("tool.files.tar.gz.sig").as_deref(),
+            Some("files.sig")
+        );
+        assert_eq!(database_file("tool-1.2.0-1-x86_64.pkg.tar.gz"), None);
+        assert_eq!(database_file("tool-1.2.0-1-x86_64.pkg.tar.zst.sig"), None);
+    }
+}
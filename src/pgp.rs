@@ -1,11 +1,11 @@
-use std::{fs, io::Write};
+use std::{fs, io::Write, path::Path};
 
 use anyhow::Result;
 use axum::{Router, extract::State, routing::get};
 use sequoia_openpgp::{
     armor::Kind,
     cert::prelude::*,
-    crypto::Password,
+    crypto::{KeyPair, Password},
     parse::Parse,
     policy::StandardPolicy,
     serialize::{
@@ -26,24 +26,26 @@ fn generate_keys(passphrase: &Password) -> Result<Cert> {
     Ok(cert)
 }
 
-pub fn generate_and_save_keys(passphrase: &Password) -> Result<Cert> {
+/// Generate the OpenPGP certificate and save it to the `path`.
+pub fn generate_and_save_keys(passphrase: &Password, path: &Path) -> Result<Cert> {
     let cert = generate_keys(passphrase)?;
 
     let key = cert.as_tsk().to_vec()?;
 
-    fs::write("key.gpg", key)?;
+    fs::write(path, key)?;
 
     Ok(cert)
 }
 
-pub fn load_cert_from_file() -> Result<Cert> {
-    let key = fs::read("key.gpg")?;
+pub fn load_cert_from_file(path: &Path) -> Result<Cert> {
+    let key = fs::read(path)?;
     let cert = Cert::from_bytes(&key)?;
 
     Ok(cert)
 }
 
-pub fn clearsign_metadata(data: &str, cert: &Cert, passphrase: &Password) -> Result<Vec<u8>> {
+/// Decrypt the signing key of the `cert`.
+fn signing_keypair(cert: &Cert, passphrase: &Password) -> Result<KeyPair> {
     let binding = StandardPolicy::new();
     let key = cert
         .keys()
@@ -61,6 +63,12 @@ pub fn clearsign_metadata(data: &str, cert: &Cert, passphrase: &Password) -> Res
     let decrypted_key = key.decrypt_secret(passphrase)?;
     let keypair = decrypted_key.into_keypair()?;
 
+    Ok(keypair)
+}
+
+pub fn clearsign_metadata(data: &str, cert: &Cert, passphrase: &Password) -> Result<Vec<u8>> {
+    let keypair = signing_keypair(cert, passphrase)?;
+
     let mut sink = vec![];
     let message = Message::new(&mut sink);
     let mut signer = Signer::new(message, keypair)?.cleartext().build()?;
@@ -76,22 +84,7 @@ pub fn detached_sign_metadata(
     cert: &Cert,
     passphrase: &Password,
 ) -> Result<Vec<u8>> {
-    let binding = StandardPolicy::new();
-    let key = cert
-        .keys()
-        .secret()
-        .with_policy(&binding, None)
-        .supported()
-        .alive()
-        .revoked(false)
-        .for_signing()
-        .next()
-        .unwrap()
-        .key()
-        .clone();
-
-    let decrypted_key = key.decrypt_secret(passphrase)?;
-    let keypair = decrypted_key.into_keypair()?;
+    let keypair = signing_keypair(cert, passphrase)?;
 
     let mut sink = vec![];
     let message = Armorer::new(Message::new(&mut sink))
@@ -105,6 +98,22 @@ pub fn detached_sign_metadata(
     Ok(sink)
 }
 
+/// Create a binary (not armored) detached signature of the `data`.
+///
+/// Pacman only accepts binary signatures.
+pub fn detached_sign_data(data: &[u8], cert: &Cert, passphrase: &Password) -> Result<Vec<u8>> {
+    let keypair = signing_keypair(cert, passphrase)?;
+
+    let mut sink = vec![];
+    let message = Message::new(&mut sink);
+    let mut signer = Signer::new(message, keypair)?.detached().build()?;
+
+    signer.write_all(data)?;
+    signer.finalize()?;
+
+    Ok(sink)
+}
+
 /////////////////////////////////////// Axum handlers /////////////////////////////////////////////////
 
 async fn armored_public_key_handler(State(state): State<AppState>) -> Vec<u8> {
@@ -115,10 +124,15 @@ async fn dearmored_public_key_handler(State(state): State<AppState>) -> Vec<u8>
     state.dearmored_public_key()
 }
 
+async fn rsa_public_key_handler(State(state): State<AppState>) -> Vec<u8> {
+    state.rsa_public_key()
+}
+
 pub fn keys() -> Router<AppState> {
     Router::new()
         .route("/packhub.asc", get(armored_public_key_handler))
         .route("/packhub.gpg", get(dearmored_public_key_handler))
+        .route("/packhub.rsa.pub", get(rsa_public_key_handler))
 }
 
 #[cfg(test)]
@@ -149,14 +163,11 @@ mod tests {
 
         fn check(&mut self, structure: MessageStructure<'_>) -> sequoia_openpgp::Result<()> {
             for layer in structure.into_iter() {
-                match layer {
-                    MessageLayer::SignatureGroup { ref results } => {
-                        // Simply check if all signatures are valid
-                        if !results.iter().any(|r| r.is_ok()) {
-                            return Err(anyhow::anyhow!("No valid signature"));
-                        }
-                    }
-                    _ => {}
+                // Simply check if all signatures are valid
+                if let MessageLayer::SignatureGroup { ref results } = layer
+                    && !results.iter().any(|r| r.is_ok())
+                {
+                    return Err(anyhow::anyhow!("No valid signature"));
                 }
             }
             Ok(())
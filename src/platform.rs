@@ -1,111 +1,312 @@
-use std::collections::{HashMap, HashSet};
+use std::collections::BTreeMap;
 use std::sync::LazyLock;
+use std::time::Duration;
 
+use anyhow::Result;
+use bson::doc;
+use chrono::{DateTime, Utc};
 use lenient_semver::parse;
+use mongodb::{Client, Collection};
 use regex::Regex;
-use semver::{Version, VersionReq};
+use semver::Version;
+use serde::{Deserialize, Serialize};
+use tracing::{info, warn};
 
-use crate::{REQWEST, utils::Dist};
+use crate::{REQWEST, state::AppState, utils::Dist};
 
 static PRE_RELEASE_STRIPER: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"(\d+)\D").unwrap());
 static APT: LazyLock<Regex> = LazyLock::new(|| Regex::new(r#"Debian APT.+\((.+)\)"#).unwrap());
-static FEDORA: LazyLock<Regex> =
-    LazyLock::new(|| Regex::new(r#"libdnf \(Fedora Linux (\d+);"#).unwrap());
-static TUMBLEWEED: LazyLock<Regex> = LazyLock::new(|| Regex::new(r#"ZYpp.+"#).unwrap());
+
+/// User-agents of the RPM package managers and the distributions they identify.
+///
+/// The first capture group, if any, is the version of the distribution.
+/// The first matching pattern wins.
+static RPM_AGENTS: LazyLock<Vec<(Regex, Dist)>> = LazyLock::new(|| {
+    // libdnf, dnf5 and tdnf tell the `NAME` and `VERSION_ID` of the os-release file,
+    // e.g. `libdnf (Fedora Linux 39; container; Linux.x86_64)`
+    let os_release = |name: &str| {
+        Regex::new(&format!(
+            r"(?:libdnf5?|tdnf)[^(]*\({name} (\d+(?:\.\d+)?)[;)]"
+        ))
+        .unwrap()
+    };
+
+    vec![
+        (os_release("Fedora(?: Linux)?"), Dist::Fedora(None)),
+        (os_release("Red Hat Enterprise Linux"), Dist::Rhel(None)),
+        (os_release("Rocky Linux"), Dist::Rocky(None)),
+        (os_release("AlmaLinux"), Dist::Alma(None)),
+        (os_release("CentOS(?: Stream| Linux)?"), Dist::CentOS(None)),
+        (os_release("Amazon Linux"), Dist::AmazonLinux(None)),
+        (os_release("Mageia"), Dist::Mageia(None)),
+        // yum 3 only shipped with RHEL 7 and its rebuilds, e.g. `urlgrabber/3.10 yum/3.4.3`
+        (Regex::new(r"yum/3\.").unwrap(), Dist::rhel("7")),
+        // zypper tells the base product, e.g. `ZYpp 17.31.15 (curl 8.5.0) openSUSE-Leap-15.5-x86_64`
+        (
+            Regex::new(r"ZYpp.+openSUSE-Leap-(\d+\.\d+)").unwrap(),
+            Dist::Leap(None),
+        ),
+        // SUSE Linux Enterprise shares the packages of Leap.
+        (
+            Regex::new(r"ZYpp.+SLES-(\d+\.\d+)").unwrap(),
+            Dist::Leap(None),
+        ),
+        (Regex::new(r"ZYpp.+").unwrap(), Dist::Tumbleweed),
+    ]
+});
+
+/// Codenames of the Ubuntu and Debian releases, which derivatives tell in their
+/// os-release file (`UBUNTU_CODENAME`, `DEBIAN_CODENAME` or `VERSION_CODENAME`).
+const APT_CODENAMES: &[(&str, &str, &str)] = &[
+    ("ubuntu", "bionic", "18.04"),
+    ("ubuntu", "focal", "20.04"),
+    ("ubuntu", "jammy", "22.04"),
+    ("ubuntu", "kinetic", "22.10"),
+    ("ubuntu", "lunar", "23.04"),
+    ("ubuntu", "mantic", "23.10"),
+    ("ubuntu", "noble", "24.04"),
+    ("ubuntu", "oracular", "24.10"),
+    ("ubuntu", "plucky", "25.04"),
+    ("ubuntu", "questing", "25.10"),
+    ("debian", "buster", "10"),
+    ("debian", "bullseye", "11"),
+    ("debian", "bookworm", "12"),
+    ("debian", "trixie", "13"),
+    ("debian", "forky", "14"),
+];
+
+/// The oldest version of apt shipped by each release of Ubuntu and Debian.
+///
+/// Bundled, so that apt clients are detected without reaching repology. The releases
+/// fetched from repology (see [`refresh_apt_releases`]) are added to them.
+const BUNDLED_APT_RELEASES: &[(&str, &str, &str)] = &[
+    ("ubuntu", "18.04", "1.6.1"),
+    ("ubuntu", "20.04", "2.0.2"),
+    ("ubuntu", "22.04", "2.4.5"),
+    ("ubuntu", "22.10", "2.5.3"),
+    ("ubuntu", "23.04", "2.6.0"),
+    ("ubuntu", "23.10", "2.7.3"),
+    ("ubuntu", "24.04", "2.7.14"),
+    ("ubuntu", "24.10", "2.9.8"),
+    ("ubuntu", "25.04", "3.0.0"),
+    ("ubuntu", "25.10", "3.1.6"),
+    ("debian", "10", "1.8.2"),
+    ("debian", "11", "2.2.4"),
+    ("debian", "12", "2.6.1"),
+    ("debian", "13", "3.0.3"),
+];
+
+/// A release of Ubuntu or Debian and a version of apt it ships.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct AptRelease {
+    distro: String,
+    release: String,
+    apt: String,
+}
+
+/// Releases fetched from repology, saved so that they survive restarts.
+#[derive(Serialize, Deserialize)]
+pub struct AptReleases {
+    #[serde(with = "mongodb::bson::serde_helpers::chrono_datetime_as_bson_datetime")]
+    updated_at: DateTime<Utc>,
+    releases: Vec<AptRelease>,
+}
+
+impl AptReleases {
+    pub async fn retrieve_from(collection: &Collection<AptReleases>) -> Result<Option<Self>> {
+        Ok(collection.find_one(doc! {}).await?)
+    }
+
+    pub async fn save_to(&self, collection: &Collection<AptReleases>) -> Result<()> {
+        collection.replace_one(doc! {}, self).upsert(true).await?;
+
+        Ok(())
+    }
+
+    pub fn releases(&self) -> &[AptRelease] {
+        &self.releases
+    }
+}
+
+/// Collection of the releases fetched from repology.
+pub fn apt_releases_collection(db: &Client) -> Collection<AptReleases> {
+    db.database("packhub").collection("apt_releases")
+}
 
 /// Detects platform based on the user-agent string of `apt` package manager.
 pub struct AptPlatformDetection {
-    ubuntu: HashMap<VersionReq, Dist>,
-    debian: HashMap<VersionReq, Dist>,
+    /// Releases and the oldest version of apt they ship, ordered by the version.
+    ubuntu: Vec<(Version, Dist)>,
+    debian: Vec<(Version, Dist)>,
 }
 
 impl AptPlatformDetection {
-    pub async fn initialize() -> Self {
-        let data = REQWEST
-            .get("https://repology.org/api/v1/project/apt")
-            .send()
-            .await
-            .unwrap()
-            .text()
-            .await
-            .unwrap();
-
-        let data: serde_json::Value = serde_json::from_str(&data).unwrap();
-
-        // HashSet to remove duplicates
-        let mut map: HashMap<&str, HashSet<Version>> = HashMap::new();
-
-        for item in data.as_array().unwrap() {
-            let repo = item["repo"].as_str().unwrap();
-            if repo.starts_with("ubuntu") || repo.starts_with("debian") {
-                let repo = repo.trim_end_matches("_proposed");
-                let ver = item["version"].as_str().unwrap();
-                let parsed = fresh_version(parse(ver).unwrap());
-                map.entry(repo).or_default().insert(parsed);
-            }
+    /// Create the detection from the bundled releases and the `releases` fetched from repology.
+    pub fn new(releases: &[AptRelease]) -> Self {
+        let bundled = BUNDLED_APT_RELEASES.iter().copied();
+        let fetched = releases.iter().map(|release| {
+            (
+                release.distro.as_str(),
+                release.release.as_str(),
+                release.apt.as_str(),
+            )
+        });
+
+        let mut oldest: BTreeMap<(&str, &str), Version> = BTreeMap::new();
+
+        for (distro, release, apt) in bundled.chain(fetched) {
+            let Ok(apt) = parse(apt) else {
+                continue;
+            };
+            let apt = fresh_version(apt);
+
+            oldest
+                .entry((distro, release))
+                .and_modify(|version| {
+                    if apt < *version {
+                        *version = apt.clone();
+                    }
+                })
+                .or_insert(apt);
         }
 
-        let mut ubuntu = HashMap::new();
-        let mut debian = HashMap::new();
-
-        for (key, value) in map.into_iter() {
-            let mut versions = value.into_iter().collect::<Vec<Version>>();
-            versions.sort();
-            let requirement;
-
-            if versions.len() > 1 {
-                requirement = VersionReq::parse(&format!(
-                    ">={}, <={}",
-                    versions[0],
-                    versions[versions.len() - 1]
-                ))
-                .unwrap();
-            } else {
-                requirement = VersionReq::parse(&format!("={}", versions[0])).unwrap();
-            }
+        let mut ubuntu = Vec::new();
+        let mut debian = Vec::new();
 
-            if key.starts_with("ubuntu") {
-                let ver = key.trim_start_matches("ubuntu_");
-                ubuntu.insert(requirement, Dist::ubuntu(&ver.replace("_", ".")));
-            } else if key.starts_with("debian") {
-                let ver = key.trim_start_matches("debian_");
-                debian.insert(requirement, Dist::debian(ver));
+        for ((distro, release), apt) in oldest {
+            match distro {
+                "ubuntu" => ubuntu.push((apt, Dist::ubuntu(release))),
+                "debian" => debian.push((apt, Dist::debian(release))),
+                _ => {}
             }
         }
 
+        ubuntu.sort();
+        debian.sort();
+
         Self { ubuntu, debian }
     }
 
     pub fn detect_ubuntu_for_apt(&self, agent: &str) -> Dist {
-        let ver = get_apt_version(agent);
-        let mut dist = Dist::Ubuntu(None);
+        detect_release(&self.ubuntu, agent).unwrap_or(Dist::Ubuntu(None))
+    }
+
+    pub fn detect_debian_for_apt(&self, agent: &str) -> Dist {
+        detect_release(&self.debian, agent).unwrap_or(Dist::Debian(None))
+    }
+}
+
+/// The newest of the `releases` shipping the apt of the `agent` or an older one.
+///
+/// So an apt newer than the known ones, like the one of a new release, is taken
+/// for the newest known release.
+fn detect_release(releases: &[(Version, Dist)], agent: &str) -> Option<Dist> {
+    let Some(apt) = get_apt_version(agent).and_then(|ver| parse(ver).ok()) else {
+        warn!("Unknown apt version in the user-agent: {agent}");
+        return None;
+    };
+    let apt = fresh_version(apt);
+
+    releases
+        .iter()
+        .rev()
+        .find(|(oldest, _)| *oldest <= apt)
+        .map(|(_, dist)| dist.clone())
+}
+
+/// Fetch the versions of apt in the Ubuntu and Debian repositories from repology.
+async fn fetch_apt_releases() -> Result<Vec<AptRelease>> {
+    let data = REQWEST
+        .get("https://repology.org/api/v1/project/apt")
+        .send()
+        .await?
+        .error_for_status()?
+        .text()
+        .await?;
+
+    Ok(parse_repology(&serde_json::from_str(&data)?))
+}
 
-        let apt = fresh_version(parse(ver).unwrap());
+/// Parse the releases from the repology listing of the apt packages.
+fn parse_repology(data: &serde_json::Value) -> Vec<AptRelease> {
+    let Some(items) = data.as_array() else {
+        return Vec::new();
+    };
 
-        for (matcher, dst) in self.ubuntu.iter() {
-            if matcher.matches(&apt) {
-                dist = dst.clone();
-                break;
+    items
+        .iter()
+        .filter_map(|item| {
+            let (distro, repo) = item["repo"].as_str()?.split_once('_')?;
+
+            if !matches!(distro, "ubuntu" | "debian") {
+                return None;
             }
-        }
 
-        dist
-    }
+            // e.g. `ubuntu_24_04`, `ubuntu_24_04_proposed` or `debian_12_backports`,
+            // but not `debian_unstable`.
+            let release = repo
+                .split('_')
+                .take_while(|part| part.chars().all(|c| c.is_ascii_digit()))
+                .collect::<Vec<_>>()
+                .join(".");
 
-    pub fn detect_debian_for_apt(&self, agent: &str) -> Dist {
-        let ver = get_apt_version(agent);
-        let mut dist = Dist::Debian(None);
+            if release.is_empty() {
+                return None;
+            }
 
-        let apt = fresh_version(parse(ver).unwrap());
+            Some(AptRelease {
+                distro: distro.to_owned(),
+                release,
+                apt: item["version"].as_str()?.to_owned(),
+            })
+        })
+        .collect()
+}
 
-        for (matcher, dst) in self.debian.iter() {
-            if matcher.matches(&apt) {
-                dist = dst.clone();
-                break;
+/// Refresh the apt releases from repology every `interval`.
+///
+/// The releases are saved, so other instances and restarts skip the refresh
+/// until they're `interval` old.
+pub async fn refresh_apt_releases(state: AppState, interval: Duration) {
+    let collection = apt_releases_collection(state.db());
+    let mut ticker = tokio::time::interval(interval);
+
+    loop {
+        ticker.tick().await;
+
+        match AptReleases::retrieve_from(&collection).await {
+            Ok(Some(saved))
+                if (Utc::now() - saved.updated_at)
+                    .to_std()
+                    .is_ok_and(|age| age < interval) =>
+            {
+                state.set_apt_platform(AptPlatformDetection::new(&saved.releases));
+                continue;
             }
+            Ok(_) => {}
+            Err(e) => warn!("Unable to retrieve the saved apt releases: {e}"),
         }
 
-        dist
+        let releases = match fetch_apt_releases().await {
+            Ok(releases) => AptReleases {
+                updated_at: Utc::now(),
+                releases,
+            },
+            Err(e) => {
+                warn!("Unable to refresh the apt releases from repology: {e}");
+                continue;
+            }
+        };
+
+        if let Err(e) = releases.save_to(&collection).await {
+            warn!("Unable to save the apt releases: {e}");
+        }
+
+        info!(
+            "Refreshed {} apt releases from repology",
+            releases.releases.len()
+        );
+        state.set_apt_platform(AptPlatformDetection::new(&releases.releases));
     }
 }
 
@@ -126,37 +327,87 @@ fn fresh_version(mut ver: Version) -> Version {
     ver
 }
 
-fn get_apt_version(agent: &str) -> &str {
-    APT.captures(agent).unwrap().get(1).unwrap().as_str()
+fn get_apt_version(agent: &str) -> Option<&str> {
+    Some(APT.captures(agent)?.get(1)?.as_str())
 }
 
-/// Retrieve the fedora version from the user-agent string.
-pub fn get_fedora_version(agent: &str) -> Option<&str> {
-    Some(FEDORA.captures(agent)?.get(1)?.as_str())
+/// Detect the distribution of an RPM package manager from its user-agent string.
+pub fn detect_rpm_os(agent: &str) -> Option<Dist> {
+    RPM_AGENTS.iter().find_map(|(re, dist)| {
+        let caps = re.captures(agent)?;
+        let mut dist = dist.clone();
+
+        if let Some(version) = caps.get(1) {
+            dist.set_version(Some(version.as_str()));
+        }
+
+        Some(dist)
+    })
 }
 
-/// Detect the opensuse fa from the user-agent string.
-pub fn detect_opensuse_tumbleweed(agent: &str) -> bool {
-    TUMBLEWEED.is_match(agent)
+/// Whether the package manager of the `agent` follows redirects to the forge.
+///
+/// The RPM package managers do, as does apt since 1.5, when it started supporting https
+/// without `apt-transport-https`.
+pub fn follows_redirects(agent: &str) -> bool {
+    if let Some(apt) = get_apt_version(agent).and_then(|ver| parse(ver).ok()) {
+        return fresh_version(apt) >= Version::new(1, 5, 0);
+    }
+
+    detect_rpm_os(agent).is_some()
 }
 
-pub fn detect_rpm_os(agent: &str) -> Option<Dist> {
-    if let Some(ver) = get_fedora_version(agent) {
-        Some(Dist::fedora(ver))
-    } else if detect_opensuse_tumbleweed(agent) {
-        Some(Dist::Tumbleweed)
-    } else {
-        None
+/// The distribution of an apt repository path with an explicit release,
+/// a version (`24.04`) or a codename (`noble`).
+pub fn apt_release_dist(distro: &str, release: &str) -> Option<Dist> {
+    let version = APT_CODENAMES
+        .iter()
+        .find(|(dist, codename, _)| *dist == distro && *codename == release)
+        .map(|(_, _, version)| *version)
+        .or_else(|| {
+            release
+                .starts_with(|c: char| c.is_ascii_digit())
+                .then_some(release)
+        })?;
+
+    parse(version).ok()?;
+
+    match distro {
+        "ubuntu" => Some(Dist::ubuntu(version)),
+        "debian" => Some(Dist::debian(version)),
+        _ => None,
     }
 }
 
+/// The distribution from the `ID` and `VERSION_ID` of the os-release file.
+///
+/// The version is the `$releasever` of dnf and zypper.
+pub fn os_release_dist(id: &str, version: &str) -> Option<Dist> {
+    let mut dist = match id {
+        "fedora" => Dist::Fedora(None),
+        "rhel" => Dist::Rhel(None),
+        "rocky" => Dist::Rocky(None),
+        "almalinux" => Dist::Alma(None),
+        "centos" => Dist::CentOS(None),
+        "amzn" => Dist::AmazonLinux(None),
+        "mageia" => Dist::Mageia(None),
+        "opensuse-leap" | "sles" => Dist::Leap(None),
+        "opensuse-tumbleweed" | "opensuse-slowroll" => Dist::Tumbleweed,
+        _ => return None,
+    };
+
+    dist.set_version(Some(version));
+
+    Some(dist)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
-    #[tokio::test]
-    async fn test_match_platform() {
-        let platform = AptPlatformDetection::initialize().await;
+    #[test]
+    fn test_match_platform() {
+        let platform = AptPlatformDetection::new(&[]);
 
         // Ubuntu
         assert_eq!(
@@ -185,10 +436,10 @@ mod tests {
         );
 
         // Debian
-        // assert_eq!(
-        //     platform.detect_debian_for_apt("Debian APT-HTTP/1.3 (1.8.2.3)"),
-        //     Dist::debian("10")
-        // );
+        assert_eq!(
+            platform.detect_debian_for_apt("Debian APT-HTTP/1.3 (1.8.2.3)"),
+            Dist::debian("10")
+        );
         assert_eq!(
             platform.detect_debian_for_apt("Debian APT-HTTP/1.3 (2.2.4)"),
             Dist::debian("11")
@@ -201,30 +452,176 @@ mod tests {
         //     platform.detect_debian_for_apt("Debian APT-HTTP/1.3 (2.9.23)"),
         //     Dist::Debian(Some("13".to_owned()))
         // );
+
+        // Newer than the known releases
+        assert_eq!(
+            platform.detect_ubuntu_for_apt("Debian APT-HTTP/1.3 (9.0.0)"),
+            Dist::ubuntu("25.10")
+        );
+
+        // Unknown
+        assert_eq!(
+            platform.detect_ubuntu_for_apt("Debian APT-HTTP/1.3 (1.0.1ubuntu2)"),
+            Dist::Ubuntu(None)
+        );
+        assert_eq!(
+            platform.detect_debian_for_apt("curl/8.5.0"),
+            Dist::Debian(None)
+        );
     }
 
     #[test]
-    fn test_apt_version() {
-        assert_eq!(get_apt_version("Debian APT-HTTP/1.3 (2.5.3)"), "2.5.3");
+    fn test_repology_releases() {
+        let data = serde_json::json!([
+            { "repo": "ubuntu_26_04", "version": "3.2.1" },
+            { "repo": "ubuntu_26_04_proposed", "version": "3.2.2" },
+            { "repo": "debian_unstable", "version": "3.3.0" },
+            { "repo": "debian_12_backports", "version": "2.7.0" },
+            { "repo": "fedora_41", "version": "0.5.15" },
+        ]);
+        let releases = parse_repology(&data);
+
+        assert_eq!(
+            releases
+                .iter()
+                .map(|r| (r.distro.as_str(), r.release.as_str(), r.apt.as_str()))
+                .collect::<Vec<_>>(),
+            [
+                ("ubuntu", "26.04", "3.2.1"),
+                ("ubuntu", "26.04", "3.2.2"),
+                ("debian", "12", "2.7.0"),
+            ]
+        );
+
+        let platform = AptPlatformDetection::new(&releases);
+
+        assert_eq!(
+            platform.detect_ubuntu_for_apt("Debian APT-HTTP/1.3 (3.2.2)"),
+            Dist::ubuntu("26.04")
+        );
+        // The bundled oldest version is kept
+        assert_eq!(
+            platform.detect_debian_for_apt("Debian APT-HTTP/1.3 (2.6.1)"),
+            Dist::debian("12")
+        );
+    }
+
+    #[test]
+    fn test_apt_release_dist() {
+        assert_eq!(
+            apt_release_dist("ubuntu", "24.04"),
+            Some(Dist::ubuntu("24.04"))
+        );
+        assert_eq!(
+            apt_release_dist("ubuntu", "noble"),
+            Some(Dist::ubuntu("24.04"))
+        );
+        assert_eq!(apt_release_dist("debian", "12"), Some(Dist::debian("12")));
+        assert_eq!(
+            apt_release_dist("debian", "bookworm"),
+            Some(Dist::debian("12"))
+        );
+        assert_eq!(apt_release_dist("debian", "noble"), None);
+        assert_eq!(apt_release_dist("debian", "kali-rolling"), None);
+        assert_eq!(apt_release_dist("mint", "22"), None);
     }
 
     #[test]
-    fn test_fedora_version() {
+    fn test_os_release_dist() {
+        let dist = |id, version| os_release_dist(id, version).map(|dist| dist.to_string());
+
+        assert_eq!(dist("fedora", "41").as_deref(), Some("fedora-41.0.0"));
+        assert_eq!(dist("almalinux", "9").as_deref(), Some("alma-9.0.0"));
         assert_eq!(
-            get_fedora_version("libdnf (Fedora Linux 38; container; Linux.x86_64)"),
-            Some("38")
+            dist("opensuse-leap", "15.6").as_deref(),
+            Some("leap-15.6.0")
         );
         assert_eq!(
-            get_fedora_version("libdnf (Fedora Linux 39; container; Linux.x86_64)"),
-            Some("39")
+            dist("opensuse-tumbleweed", "20241018").as_deref(),
+            Some("tumbleweed")
         );
+        assert_eq!(dist("ubuntu", "24.04"), None);
     }
 
     #[test]
-    fn test_detect_opensuse() {
-        assert!(detect_opensuse_tumbleweed(
-            "ZYpp 17.31.15 (curl 8.5.0) openSUSE-Tumbleweed-x86_64"
+    fn test_apt_version() {
+        assert_eq!(
+            get_apt_version("Debian APT-HTTP/1.3 (2.5.3)"),
+            Some("2.5.3")
+        );
+        assert_eq!(get_apt_version("Wget/1.21"), None);
+    }
+
+    #[test]
+    fn test_follows_redirects() {
+        assert!(follows_redirects("Debian APT-HTTP/1.3 (2.6.1)"));
+        assert!(follows_redirects("Debian APT-HTTP/1.3 (1.6.12ubuntu0.2)"));
+        assert!(!follows_redirects("Debian APT-HTTP/1.3 (1.2.35)"));
+        assert!(follows_redirects(
+            "libdnf (Fedora Linux 40; container; Linux.x86_64)"
         ));
-        assert!(detect_opensuse_tumbleweed("ZYpp 17.37.17 (curl 8.15.0)"));
+        assert!(follows_redirects("urlgrabber/3.10 yum/3.4.3"));
+        assert!(!follows_redirects("Wget/1.21"));
+        assert!(!follows_redirects(""));
+    }
+
+    #[test]
+    fn test_detect_rpm_os() {
+        let detect = |agent| detect_rpm_os(agent).map(|dist| dist.to_string());
+
+        for (agent, dist) in [
+            (
+                "libdnf (Fedora Linux 38; container; Linux.x86_64)",
+                "fedora-38.0.0",
+            ),
+            (
+                "libdnf (Fedora Linux 39; container; Linux.x86_64)",
+                "fedora-39.0.0",
+            ),
+            (
+                "libdnf5 (Fedora Linux 41; workstation; Linux.x86_64)",
+                "fedora-41.0.0",
+            ),
+            (
+                "libdnf (Red Hat Enterprise Linux 9.4; generic; Linux.x86_64)",
+                "rhel-9.4.0",
+            ),
+            (
+                "libdnf (Rocky Linux 9.3; generic; Linux.x86_64)",
+                "rocky-9.3.0",
+            ),
+            (
+                "libdnf (AlmaLinux 8.10; generic; Linux.aarch64)",
+                "alma-8.10.0",
+            ),
+            (
+                "libdnf (CentOS Stream 9; generic; Linux.x86_64)",
+                "centos-9.0.0",
+            ),
+            (
+                "libdnf (Amazon Linux 2023; generic; Linux.x86_64)",
+                "amazonlinux-2023.0.0",
+            ),
+            ("libdnf (Mageia 9; generic; Linux.x86_64)", "mageia-9.0.0"),
+            (
+                "tdnf (Fedora Linux 40; generic; Linux.x86_64)",
+                "fedora-40.0.0",
+            ),
+            ("urlgrabber/3.10 yum/3.4.3", "rhel-7.0.0"),
+            (
+                "ZYpp 17.31.15 (curl 8.5.0) openSUSE-Tumbleweed-x86_64",
+                "tumbleweed",
+            ),
+            ("ZYpp 17.37.17 (curl 8.15.0)", "tumbleweed"),
+            (
+                "ZYpp 17.31.31 (curl 8.0.1) openSUSE-Leap-15.5-x86_64",
+                "leap-15.5.0",
+            ),
+            ("ZYpp 17.31.31 (curl 8.0.1) SLES-15.6-x86_64", "leap-15.6.0"),
+        ] {
+            assert_eq!(detect(agent).as_deref(), Some(dist), "{agent}");
+        }
+
+        assert_eq!(detect("Debian APT-HTTP/1.3 (2.6.1)"), None);
     }
 }
@@ -0,0 +1,444 @@
+//! Delivery of the packages from the forges, proxied or redirected to.
+//!
+//! The proxied packages are verified against the SHA256 recorded in their saved metadata,
+//! which is what the repository indices advertise to the clients.
+use std::{error::Error, fmt::Display, str::FromStr};
+
+use anyhow::{Context, bail};
+use axum::{
+    BoxError,
+    body::{Body, Bytes},
+    http::{
+        HeaderMap, HeaderName, Method, StatusCode,
+        header::{
+            ACCEPT_RANGES, CONTENT_LENGTH, CONTENT_RANGE, CONTENT_TYPE, ETAG, IF_MODIFIED_SINCE,
+            IF_NONE_MATCH, IF_RANGE, LAST_MODIFIED, LOCATION, RANGE, USER_AGENT,
+        },
+    },
+    response::{IntoResponse, Response},
+};
+use futures_util::{Stream, StreamExt, stream};
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+use tracing::{debug, error};
+
+use crate::{
+    REQWEST,
+    blob::stored_package,
+    db::{PackageMetadata, metadata_collection},
+    error::{AppError, PackhubError},
+    platform::follows_redirects,
+    source::Forge,
+    state::AppState,
+};
+
+/// Headers of the package requests forwarded to the forge.
+const FORWARDED_REQUEST_HEADERS: [HeaderName; 4] =
+    [RANGE, IF_RANGE, IF_NONE_MATCH, IF_MODIFIED_SINCE];
+
+/// Headers of the responses of the forge forwarded to the clients.
+const FORWARDED_RESPONSE_HEADERS: [HeaderName; 6] = [
+    CONTENT_TYPE,
+    CONTENT_LENGTH,
+    CONTENT_RANGE,
+    ACCEPT_RANGES,
+    ETAG,
+    LAST_MODIFIED,
+];
+
+/// How the packages of a repository are delivered to the clients.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DownloadMode {
+    /// Stream the packages through packhub.
+    Proxy,
+    /// Redirect the clients to the assets on the forge, saving the bandwidth of packhub.
+    Redirect,
+}
+
+impl FromStr for DownloadMode {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "proxy" => Ok(DownloadMode::Proxy),
+            "redirect" => Ok(DownloadMode::Redirect),
+            _ => bail!("Unknown download mode: {s}"),
+        }
+    }
+}
+
+/// Split the path of a package into the tag of its release and its file name.
+///
+/// Tags can contain `/`, but file names can't.
+pub fn release_asset(path: &str) -> Result<(&str, &str), AppError> {
+    path.rsplit_once('/')
+        .filter(|(tag, file)| !tag.is_empty() && !file.is_empty())
+        .ok_or_else(|| PackhubError::NotFound(format!("Unknown file requested: {path}")).into())
+}
+
+/// Respond with the package `file` released with the `tag`.
+///
+/// In the redirect mode, clients known to follow redirects are redirected to the asset on
+/// the forge. Otherwise the package is served from the blob store or proxied.
+pub async fn package_response(
+    state: &AppState,
+    (forge, owner, repo): (Forge, &str, &str),
+    tag: &str,
+    file: &str,
+    (method, headers): (&Method, &HeaderMap),
+) -> Result<Response, AppError> {
+    let agent = headers
+        .get(USER_AGENT)
+        .and_then(|agent| agent.to_str().ok())
+        .unwrap_or_default();
+
+    let redirect = state.download_mode(forge, owner, repo) == DownloadMode::Redirect;
+
+    if redirect && follows_redirects(agent) {
+        let url = state
+            .source(forge)
+            .asset_url(owner, repo, tag, file)
+            .await?;
+        debug!("Redirecting to the package: {url}");
+
+        return Ok((StatusCode::FOUND, [(LOCATION, url)]).into_response());
+    }
+
+    if let Some(res) = stored_package(state, (forge, owner, repo), Some(tag), file, headers).await {
+        return Ok(res);
+    }
+
+    let url = state
+        .source(forge)
+        .asset_url(owner, repo, tag, file)
+        .await?;
+
+    proxy_package(
+        state,
+        (forge, owner, repo),
+        Some(tag),
+        file,
+        &url,
+        (method, headers),
+    )
+    .await
+}
+
+/// The proxied package doesn't match the SHA256 in its saved metadata.
+#[derive(Debug, Clone)]
+pub struct ChecksumMismatch {
+    file: String,
+    expected: String,
+    actual: String,
+}
+
+impl Display for ChecksumMismatch {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "The SHA256 of the package {} is {}, but {} was expected",
+            self.file, self.actual, self.expected
+        )
+    }
+}
+
+impl Error for ChecksumMismatch {}
+
+/// Proxy the package `file` released with the version `ver` from the `url`.
+///
+/// The request `method` and its range and conditional `headers` are forwarded to the forge,
+/// so that clients can resume downloads and revalidate their copy.
+///
+/// When the whole package is downloaded and it has saved metadata, the streamed bytes are
+/// verified against its SHA256. On a mismatch the response is aborted and the metadata is
+/// deleted, so that it's extracted again from the package released on the forge.
+pub async fn proxy_package(
+    state: &AppState,
+    (forge, owner, repo): (Forge, &str, &str),
+    ver: Option<&str>,
+    file: &str,
+    url: &str,
+    (method, headers): (&Method, &HeaderMap),
+) -> Result<Response, AppError> {
+    tracing::trace!("Proxying package from: {}", url);
+    let mut req = REQWEST.request(method.clone(), url);
+    for name in FORWARDED_REQUEST_HEADERS {
+        if let Some(value) = headers.get(&name) {
+            req = req.header(&name, value);
+        }
+    }
+    let res = req
+        .send()
+        .await
+        .context("Error occurred while proxying package")?;
+    tracing::trace!("Proxying package respone: {}", res.status());
+
+    let status = res.status();
+    let mut proxied = HeaderMap::new();
+    for name in FORWARDED_RESPONSE_HEADERS {
+        if let Some(value) = res.headers().get(&name) {
+            proxied.insert(name, value.clone());
+        }
+    }
+
+    let project = format!("{owner}/{repo}");
+    let collection = metadata_collection(state, forge, &project);
+
+    // Only the whole package can be verified.
+    let released = match (method, status) {
+        (&Method::GET, StatusCode::OK) => {
+            PackageMetadata::find_released(&collection, file, ver).await
+        }
+        _ => None,
+    };
+
+    let Some((metadata, sha256)) = released else {
+        return Ok((status, proxied, Body::from_stream(res.bytes_stream())).into_response());
+    };
+
+    let verifier = Verifier::new(file, sha256, res.content_length());
+
+    let stream = verify(res.bytes_stream(), verifier, async move |mismatch| {
+        error!(
+            target: "packhub::audit",
+            event = "checksum_mismatch",
+            %forge,
+            project,
+            file = mismatch.file,
+            expected = mismatch.expected,
+            actual = mismatch.actual,
+            "Proxied package doesn't match its indexed checksum, invalidating its metadata"
+        );
+
+        if let Err(e) = metadata.delete_from(&collection).await {
+            error!("Unable to delete the metadata of {}: {e}", mismatch.file);
+        }
+    });
+
+    Ok((status, proxied, Body::from_stream(stream)).into_response())
+}
+
+/// Hashes the package while it's streamed.
+struct Verifier {
+    file: String,
+    /// Expected SHA256 of the package.
+    sha256: String,
+    hasher: Sha256,
+    /// Length of the package, if the forge sent it.
+    len: Option<u64>,
+    received: u64,
+}
+
+impl Verifier {
+    fn new(file: &str, sha256: String, len: Option<u64>) -> Self {
+        Self {
+            file: file.to_owned(),
+            sha256,
+            hasher: Sha256::new(),
+            len,
+            received: 0,
+        }
+    }
+
+    fn update(&mut self, chunk: &[u8]) {
+        self.hasher.update(chunk);
+        self.received += chunk.len() as u64;
+    }
+
+    /// Whether the whole package was received.
+    fn is_complete(&self) -> bool {
+        self.len == Some(self.received)
+    }
+
+    fn finish(self) -> Result<(), ChecksumMismatch> {
+        let actual = format!("{:x}", self.hasher.finalize());
+
+        if actual == self.sha256 {
+            return Ok(());
+        }
+
+        Err(ChecksumMismatch {
+            file: self.file,
+            expected: self.sha256,
+            actual,
+        })
+    }
+}
+
+/// Verify the `stream` of a package with the `verifier`.
+///
+/// The stream ends with a [`ChecksumMismatch`] error when the package doesn't match, once
+/// `on_mismatch` has run.
+///
+/// When the length of the package is known, its last chunk is held back until it's verified:
+/// hyper stops polling the body once `Content-Length` bytes are written, so the end of the
+/// stream would never be reached and the client would get the whole package.
+fn verify<S, E, F, Fut>(
+    stream: S,
+    verifier: Verifier,
+    on_mismatch: F,
+) -> impl Stream<Item = Result<Bytes, BoxError>> + Send + 'static
+where
+    S: Stream<Item = Result<Bytes, E>> + Send + 'static,
+    E: Into<BoxError> + Send,
+    F: FnOnce(ChecksumMismatch) -> Fut + Send + 'static,
+    Fut: Future<Output = ()> + Send,
+{
+    let state = Some((Box::pin(stream), verifier, on_mismatch));
+
+    stream::unfold(state, async |state| {
+        let (mut stream, mut verifier, on_mismatch) = state?;
+
+        let last = match stream.next().await {
+            Some(Ok(chunk)) => {
+                verifier.update(&chunk);
+                if !verifier.is_complete() {
+                    return Some((Ok(chunk), Some((stream, verifier, on_mismatch))));
+                }
+                Some(chunk)
+            }
+            Some(Err(e)) => return Some((Err(e.into()), None)),
+            None => None,
+        };
+
+        match (verifier.finish(), last) {
+            (Ok(()), Some(chunk)) => Some((Ok(chunk), None)),
+            (Ok(()), None) => None,
+            (Err(mismatch), _) => {
+                on_mismatch(mismatch.clone()).await;
+                Some((Err(mismatch.into()), None))
+            }
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{
+        Arc,
+        atomic::{AtomicBool, Ordering},
+    };
+
+    use axum::{Router, extract::Path, routing::get};
+    use tokio::net::TcpListener;
+
+    use super::*;
+    use crate::utils::hashsum;
+
+    /// Stream of the package `package`, verified against the SHA256 of `expected`.
+    fn verified(
+        expected: &str,
+        len: Option<u64>,
+        mismatched: Arc<AtomicBool>,
+    ) -> impl Stream<Item = Result<Bytes, BoxError>> + Send + 'static {
+        let chunks = stream::iter([
+            Ok::<_, BoxError>(Bytes::from_static(b"pack")),
+            Ok(Bytes::from_static(b"age")),
+        ]);
+        let verifier = Verifier::new("tool.deb", hashsum::<Sha256>(expected.as_bytes()), len);
+
+        verify(chunks, verifier, async move |_| {
+            mismatched.store(true, Ordering::SeqCst)
+        })
+    }
+
+    #[test]
+    fn test_release_asset() {
+        assert_eq!(
+            release_asset("v1.0.0/tool_1.0.0_amd64.deb").ok(),
+            Some(("v1.0.0", "tool_1.0.0_amd64.deb"))
+        );
+        assert_eq!(
+            release_asset("release/1.0/tool-1.0-1.x86_64.rpm").ok(),
+            Some(("release/1.0", "tool-1.0-1.x86_64.rpm"))
+        );
+        assert!(release_asset("tool_1.0.0_amd64.deb").is_err());
+        assert!(release_asset("v1.0.0/").is_err());
+    }
+
+    #[test]
+    fn test_download_mode() {
+        assert_eq!(
+            "proxy".parse::<DownloadMode>().unwrap(),
+            DownloadMode::Proxy
+        );
+        assert_eq!(
+            "redirect".parse::<DownloadMode>().unwrap(),
+            DownloadMode::Redirect
+        );
+        assert!("mirror".parse::<DownloadMode>().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_verify() {
+        let mismatched = Arc::new(AtomicBool::new(false));
+        let chunks: Vec<_> = verified("package", None, mismatched.clone())
+            .collect()
+            .await;
+
+        assert_eq!(chunks.len(), 2);
+        assert!(chunks.iter().all(Result::is_ok));
+        assert!(!mismatched.load(Ordering::SeqCst));
+
+        let chunks: Vec<_> = verified("package", Some(7), mismatched.clone())
+            .collect()
+            .await;
+
+        assert_eq!(chunks.len(), 2);
+        assert!(chunks.iter().all(Result::is_ok));
+        assert!(!mismatched.load(Ordering::SeqCst));
+
+        let chunks: Vec<_> = verified("replaced", None, mismatched.clone())
+            .collect()
+            .await;
+
+        assert_eq!(chunks.len(), 3);
+        let error = chunks.last().unwrap().as_ref().unwrap_err();
+        assert!(error.is::<ChecksumMismatch>());
+        assert!(error.to_string().contains("tool.deb"));
+        assert!(mismatched.load(Ordering::SeqCst));
+
+        // The last chunk of a package with a known length is held back
+        let mismatched = Arc::new(AtomicBool::new(false));
+        let chunks: Vec<_> = verified("replaced", Some(7), mismatched.clone())
+            .collect()
+            .await;
+
+        assert_eq!(chunks.len(), 2);
+        assert!(chunks[1].is_err());
+        assert!(mismatched.load(Ordering::SeqCst));
+    }
+
+    #[tokio::test]
+    async fn test_verify_response() {
+        let mismatched = Arc::new(AtomicBool::new(false));
+
+        let app = Router::new().route(
+            "/{sha256}",
+            get({
+                let mismatched = mismatched.clone();
+                async move |Path(expected): Path<String>| {
+                    let stream = verified(&expected, Some(7), mismatched);
+                    ([(CONTENT_LENGTH, "7")], Body::from_stream(stream))
+                }
+            }),
+        );
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, app).await });
+
+        let download = async |expected: &str| -> reqwest::Result<Bytes> {
+            let url = format!("http://{addr}/{expected}");
+            REQWEST.get(url).send().await?.bytes().await
+        };
+
+        assert_eq!(download("package").await.unwrap(), "package");
+        assert!(!mismatched.load(Ordering::SeqCst));
+
+        // The client doesn't get the whole package and the mismatch is handled
+        assert!(download("replaced").await.is_err());
+        assert!(mismatched.load(Ordering::SeqCst));
+    }
+}
@@ -1,81 +1,123 @@
-use anyhow::{Result, bail};
-use futures_util::TryStreamExt;
+use std::{collections::HashMap, sync::Arc};
+
+use anyhow::{Context, Result, bail};
 use mongodb::Collection;
-use octocrab::{Octocrab, models::repos::Release};
-use tokio::{pin, task::JoinSet};
-use tracing::{debug, error};
+use semver::VersionReq;
+use tokio::task::JoinSet;
+use tracing::{debug, error, warn};
 
 use crate::{
-    db::PackageMetadata,
-    package::Package,
-    platform::{AptPlatformDetection, detect_rpm_os},
-    selector::select_packages,
+    apk::ApkPackage,
+    apt::DebianPackage,
+    blob::BlobStore,
+    db::{PackageMetadata, metadata_collection},
+    error::PackhubError,
+    package::{AssetTooLarge, Data, Package},
+    pacman::PacmanPackage,
+    platform::{AptPlatformDetection, apt_release_dist, detect_rpm_os, os_release_dist},
+    rpm::RPMPackage,
+    selector::select_releases,
+    snapshot::{Snapshot, SnapshotKey, snapshot_collection},
+    source::{Forge, Release, ReleaseSource},
     state::AppState,
-    utils::ReleaseChannel,
+    utils::{Dist, ReleaseChannel, Type, tag_version},
 };
 
+/// Number of recent releases searched for a release matching a version requirement.
+const RANGE_SEARCH_DEPTH: usize = 100;
+
 pub struct Repository {
+    project: String,
+    channel: ReleaseChannel,
     collection: Collection<PackageMetadata>,
-    packages: Vec<Package>,
+    snapshots: Collection<Snapshot>,
+    /// Fingerprint of the configuration the index files are rendered with.
+    render_config: String,
+    /// Packages of each release served by the repository, newest release first.
+    releases: Vec<Vec<Package>>,
     downloaded: Vec<Package>,
-    platform: AptPlatformDetection,
+    platform: Arc<AptPlatformDetection>,
+    /// Maximum size of the downloaded packages, in bytes.
+    max_asset_size: u64,
+    blobs: Option<Arc<BlobStore>>,
 }
 
 impl Repository {
-    pub async fn from_github(
+    /// Create the repository from the release of `channel` hosted on the `forge`.
+    pub async fn from_forge(
+        forge: Forge,
         owner: &str,
         repo: &str,
-        release: &ReleaseChannel,
+        channel: &ReleaseChannel,
         state: &AppState,
-    ) -> Self {
+    ) -> Result<Self> {
         let project = format!("{owner}/{repo}");
-        let collection = state
-            .db()
-            .database("github")
-            .collection::<PackageMetadata>(&project);
+        let collection = metadata_collection(state, forge, &project);
+        let snapshots = snapshot_collection(state, forge);
+
+        let releases = channel_releases(forge, owner, repo, channel, state).await?;
 
         let mut packages = Vec::new();
 
-        let release = match release {
-            ReleaseChannel::Stable => state
-                .github()
-                .repos(owner, repo)
-                .releases()
-                .get_latest()
-                .await
-                .unwrap(),
-            ReleaseChannel::Unstable => github_latest_prerelease(state.github(), &owner, &repo)
-                .await
-                .unwrap()
-                .unwrap_or_else(|| {
-                    panic!("No pre-release found for the repository: {owner}/{repo}")
-                }),
-        };
+        for release in releases {
+            let mut release_packages = Vec::new();
+            let signatures: HashMap<_, _> = release
+                .assets
+                .iter()
+                .filter_map(|asset| Some((asset.name.strip_suffix(".sig")?, asset.url.clone())))
+                .collect();
 
-        for asset in release.assets {
-            let package = Package::detect_package(
-                &asset.name,
-                release.tag_name.clone(),
-                asset.browser_download_url.to_string(),
-                asset.updated_at,
-            );
-            if let Ok(package) = package {
-                if let Some(metadata) = PackageMetadata::retrieve_from(&collection, &package).await
-                {
-                    package.set_metadata(metadata.data());
+            for asset in &release.assets {
+                let package = Package::detect_package(
+                    &asset.name,
+                    release.tag_name.clone(),
+                    asset.url.clone(),
+                    asset.updated_at,
+                );
+                if let Ok(package) = package {
+                    // Pacman databases embed the detached signatures of the packages.
+                    if *package.ty() == Type::Pacman
+                        && let Some(url) = signatures.get(asset.name.as_str())
+                    {
+                        package.set_signature_url(url.clone());
+                    }
+                    if let Some(metadata) =
+                        PackageMetadata::retrieve_from(&collection, &package).await
+                    {
+                        // Outdated metadata is extracted anew from the downloaded package.
+                        if metadata.is_current(package.ty()) {
+                            package.set_metadata(metadata.data());
+                        } else if let Err(e) = metadata.delete_from(&collection).await {
+                            error!(
+                                "Unable to delete the outdated metadata of {:?}: {e}",
+                                package.file_name()
+                            );
+                        }
+                    }
+                    release_packages.push(package);
                 }
-                packages.push(package);
             }
-        }
 
-        let platform = AptPlatformDetection::initialize().await;
+            packages.push(release_packages);
+        }
 
-        Repository {
+        Ok(Repository {
+            project,
+            channel: channel.clone(),
             collection,
-            packages,
-            platform,
+            snapshots,
+            render_config: state.render_config().to_owned(),
+            releases: packages,
+            platform: state.apt_platform(),
             downloaded: Vec::new(),
-        }
+            max_asset_size: state.max_asset_size(),
+            blobs: state.blob_store(),
+        })
+    }
+
+    /// Packages of every release of the repository.
+    pub fn packages(&self) -> impl Iterator<Item = &Package> {
+        self.releases.iter().flatten()
     }
 
     pub async fn save_package_metadata(&mut self) {
@@ -99,51 +141,123 @@ impl Repository {
         }
     }
 
-    /// Select packages for apt based distributions.
+    /// Detect the distribution of an apt client.
     ///
     /// The `distro` parameter is the name of the distribution (`debian`, `ubuntu`).
     ///
-    /// The `agent` parameter is the user-agent string of the apt client.
-    ///
-    /// It returns a vector of packages that are compatible with the given agent.
+    /// The `release` parameter is the release of the distribution given in the
+    /// repository path, which overrides the detection from the user-agent.
     ///
-    /// It also downloads the selected packages if the metadata is not available.
-    pub async fn select_package_apt(&mut self, distro: &str, agent: &str) -> Result<Vec<Package>> {
-        let dist = match distro {
-            "ubuntu" => self.platform.detect_ubuntu_for_apt(agent),
-            "debian" => self.platform.detect_debian_for_apt(agent),
-            dist => bail!("Unknown apt distribution {dist}"),
-        };
+    /// The `agent` parameter is the user-agent string of the apt client.
+    pub fn detect_apt_dist(
+        &self,
+        distro: &str,
+        release: Option<&str>,
+        agent: &str,
+    ) -> Result<Dist> {
+        if !matches!(distro, "ubuntu" | "debian") {
+            return Err(PackhubError::UnsupportedClient(format!(
+                "Unknown apt distribution {distro}"
+            ))
+            .into());
+        }
 
-        let packages: Vec<Package> = select_packages(&self.packages, dist)
-            .into_iter()
-            .cloned()
-            .collect();
+        if let Some(release) = release {
+            if let Some(dist) = apt_release_dist(distro, release) {
+                return Ok(dist);
+            }
 
-        debug!("Packages selected {:?}", packages);
+            warn!("Unknown {distro} release {release}, detecting it from the user-agent");
+        }
 
-        self.download_packages(packages).await
+        match distro {
+            "ubuntu" => Ok(self.platform.detect_ubuntu_for_apt(agent)),
+            _ => Ok(self.platform.detect_debian_for_apt(agent)),
+        }
     }
 
-    /// Select packages for RPM based distribution.
-    ///
-    /// The `agent` parameter is the user-agent string of the rpm client.
+    /// Detect the distribution of an RPM client.
     ///
-    /// It returns a vector of packages that are compatible with the given agent.
+    /// The `release` parameter is the os-release `ID` and the `$releasever` given in the
+    /// repository path, which override the detection from the user-agent string.
+    pub fn detect_rpm_dist(&self, release: Option<(&str, &str)>, agent: &str) -> Result<Dist> {
+        if let Some((id, version)) = release {
+            if let Some(dist) = os_release_dist(id, version) {
+                return Ok(dist);
+            }
+
+            warn!("Unknown RPM distribution {id} {version}, detecting it from the user-agent");
+        }
+
+        detect_rpm_os(agent).with_context(|| {
+            PackhubError::UnsupportedClient(format!("Unknown RPM distribution agent: {agent}"))
+        })
+    }
+
+    /// Get the snapshot of the index files for the `dist`.
     ///
-    /// It also downloads the selected packages if the metadata is not available.
-    pub async fn select_package_rpm(&mut self, agent: &str) -> Result<Vec<Package>> {
-        let Some(dist) = detect_rpm_os(agent) else {
-            bail!("Unknown RPM distribution agent: {agent}");
-        };
-        let packages: Vec<Package> = select_packages(&self.packages, dist)
-            .into_iter()
-            .cloned()
-            .collect();
+    /// When the selected packages haven't been snapshotted yet, the packages are
+    /// downloaded if their metadata is not available, and `render` renders the
+    /// index files (paths and contents) from them.
+    pub async fn snapshot(
+        &mut self,
+        dist: Dist,
+        render: impl FnOnce(&[Package]) -> Result<Vec<(String, Vec<u8>)>>,
+    ) -> Result<Snapshot> {
+        let packages = select_releases(&self.releases, dist.clone());
 
         debug!("Packages selected {:?}", packages);
 
-        self.download_packages(packages).await
+        let key = SnapshotKey::new(
+            &self.render_config,
+            &self.project,
+            &self.channel,
+            &dist,
+            &packages,
+        );
+
+        if let Some(snapshot) = Snapshot::retrieve_from(&self.snapshots, &key).await? {
+            debug!("Index served from snapshot: {key:?}");
+            return Ok(snapshot.modified_by(&packages));
+        }
+
+        let packages = self.download_packages(packages).await?;
+        let files = render(&packages)?;
+        self.save_package_metadata().await;
+
+        let snapshot = Snapshot::new(key, files).save_to(&self.snapshots).await?;
+
+        Ok(snapshot.modified_by(&packages))
+    }
+
+    /// Download every package of the repository and extract its metadata.
+    ///
+    /// The metadata is saved, so that later requests don't have to download the packages.
+    /// Packages whose metadata is already available are skipped.
+    pub async fn prewarm(&mut self) -> Result<()> {
+        let packages = self.releases.iter().flatten().cloned().collect();
+
+        for package in self.download_packages(packages).await? {
+            let extracted = match package.ty() {
+                Type::Deb => DebianPackage::from_package(&package).map(|_| ()),
+                Type::Rpm => RPMPackage::from_package(&package).map(|_| ()),
+                Type::Pacman => PacmanPackage::from_package(&package).map(|_| ()),
+                Type::Apk => ApkPackage::from_package(&package).map(|_| ()),
+            };
+
+            if let Err(e) = extracted {
+                error!(
+                    "Error occurred when extracting metadata of the package {:?}: {e}",
+                    package.file_name()
+                );
+            }
+        }
+
+        self.downloaded
+            .retain(|package| package.is_metadata_available());
+        self.save_package_metadata().await;
+
+        Ok(())
     }
 
     async fn download_packages(&mut self, packages: Vec<Package>) -> Result<Vec<Package>> {
@@ -152,9 +266,21 @@ impl Repository {
 
         for package in packages {
             if !package.is_metadata_available() {
+                let max_size = self.max_asset_size;
+                let blobs = self.blobs.clone();
                 runner.spawn(async move {
                     debug!("Downloading package: {:?}", package.file_name());
-                    package.download().await.and_then(|_| Ok(package))
+                    package.download(max_size).await?;
+
+                    // Keep the package, so that it can be served without the forge.
+                    if let Some(blobs) = blobs
+                        && let Data::Package(file) = package.data()
+                        && let Err(e) = blobs.insert(file).await
+                    {
+                        warn!("Unable to store {package:?} in the blob store: {e}");
+                    }
+
+                    anyhow::Ok(package)
                 });
             } else {
                 debug!("Package metadata available: {:?}", package.file_name());
@@ -167,7 +293,15 @@ impl Repository {
                 bail!("Executor error: Failed to download package")
             };
 
-            let package = res?;
+            let package = match res {
+                Ok(package) => package,
+                // A package too large to be downloaded is left out of the repository.
+                Err(e) if e.is::<AssetTooLarge>() => {
+                    warn!("{e}");
+                    continue;
+                }
+                Err(e) => return Err(e),
+            };
 
             debug!("Downloaded package: {:?}", package.file_name());
 
@@ -181,33 +315,209 @@ impl Repository {
     }
 }
 
-/// Fetch the latest pre-release from a GitHub repository.
+/// Fetch the releases of the `channel` of a repository hosted on the `forge`.
+pub async fn channel_releases(
+    forge: Forge,
+    owner: &str,
+    repo: &str,
+    channel: &ReleaseChannel,
+    state: &AppState,
+) -> Result<Vec<Release>> {
+    let source = state.source(forge);
+
+    let releases = match channel {
+        ReleaseChannel::Stable => vec![source.latest_release(owner, repo).await?],
+        ReleaseChannel::Unstable => vec![
+            latest_prerelease(&source, owner, repo)
+                .await?
+                .with_context(|| {
+                    PackhubError::NoRelease(format!(
+                        "No pre-release found for the repository: {owner}/{repo}"
+                    ))
+                })?,
+        ],
+        ReleaseChannel::All => recent_releases(&source, owner, repo, state.all_releases()).await?,
+        ReleaseChannel::Tag(tag) => vec![source.release_by_tag(owner, repo, tag).await?],
+        ReleaseChannel::Range(req) => vec![
+            latest_in_range(&source, owner, repo, req)
+                .await?
+                .with_context(|| {
+                    PackhubError::NoRelease(format!(
+                        "No release of {owner}/{repo} matches the version {req}"
+                    ))
+                })?,
+        ],
+    };
+
+    Ok(releases)
+}
+
+/// Fetch the latest pre-release of a project.
 /// This can be a release (stable) but certainly ignores draft releases.
-async fn github_latest_prerelease(
-    octo: &Octocrab,
+async fn latest_prerelease(
+    source: &impl ReleaseSource,
     owner: &str,
     repo: &str,
 ) -> Result<Option<Release>> {
-    // GitHub returns releases in reverse-chronological order (newest first).
-    // Grab up to 10 per page and scan until we find the first release that's not a draft but can be a pre-release.
-    let stream = octo
-        .repos(owner, repo)
-        .releases()
-        .list()
-        .per_page(10)
-        .send()
-        .await?
-        .into_stream(&octo);
-
-    pin!(stream);
-
-    while let Some(release) = stream.try_next().await? {
-        if release.draft {
-            continue;
+    // Releases are listed newest first, so scan until we find
+    // the first release that's not a draft but can be a pre-release.
+    let releases = source.releases(owner, repo, 10).await?;
+
+    Ok(releases.into_iter().find(|release| !release.draft))
+}
+
+/// Fetch at most `count` of the most recent stable releases, newest first.
+///
+/// Drafts and pre-releases are skipped, so more releases are listed until `count` stable
+/// releases are found, up to `RANGE_SEARCH_DEPTH` releases beyond `count`.
+async fn recent_releases(
+    source: &impl ReleaseSource,
+    owner: &str,
+    repo: &str,
+    count: usize,
+) -> Result<Vec<Release>> {
+    let depth = count + RANGE_SEARCH_DEPTH;
+    let mut listed = count;
+
+    loop {
+        let releases = source.releases(owner, repo, listed).await?;
+        let exhausted = releases.len() < listed;
+
+        let stable: Vec<_> = releases
+            .into_iter()
+            .filter(|release| !release.draft && !release.prerelease)
+            .take(count)
+            .collect();
+
+        if stable.len() == count || exhausted || listed == depth {
+            return Ok(stable);
         }
 
-        return Ok(Some(release));
+        listed = (listed * 2).min(depth);
     }
+}
+
+/// Fetch the newest release whose tag matches the version requirement `req`.
+///
+/// Pre-releases only match when the requirement names a pre-release of the same version.
+async fn latest_in_range(
+    source: &impl ReleaseSource,
+    owner: &str,
+    repo: &str,
+    req: &VersionReq,
+) -> Result<Option<Release>> {
+    let releases = source.releases(owner, repo, RANGE_SEARCH_DEPTH).await?;
+
+    let release = releases
+        .into_iter()
+        .filter(|release| !release.draft)
+        .filter_map(|release| Some((tag_version(&release.tag_name)?, release)))
+        .filter(|(version, _)| req.matches(version))
+        .max_by(|(a, _), (b, _)| a.cmp(b))
+        .map(|(_, release)| release);
+
+    Ok(release)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A release source serving a fixed list of releases.
+    struct StaticSource(Vec<Release>);
+
+    impl ReleaseSource for StaticSource {
+        async fn latest_release(&self, owner: &str, repo: &str) -> Result<Release> {
+            let latest = self
+                .0
+                .iter()
+                .find(|release| !release.draft && !release.prerelease);
 
-    Ok(None)
+            latest.cloned().ok_or_else(|| {
+                PackhubError::NoRelease(format!("No release of {owner}/{repo} found")).into()
+            })
+        }
+
+        async fn release_by_tag(&self, owner: &str, repo: &str, tag: &str) -> Result<Release> {
+            let release = self.0.iter().find(|release| release.tag_name == tag);
+
+            release.cloned().ok_or_else(|| {
+                PackhubError::NoRelease(format!("No release {tag} of {owner}/{repo} found")).into()
+            })
+        }
+
+        async fn releases(&self, _: &str, _: &str, count: usize) -> Result<Vec<Release>> {
+            Ok(self.0.iter().take(count).cloned().collect())
+        }
+    }
+
+    fn release(tag: &str, draft: bool) -> Release {
+        Release {
+            tag_name: tag.to_owned(),
+            draft,
+            prerelease: tag.contains('-'),
+            assets: Vec::new(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_latest_in_range() {
+        let source = StaticSource(vec![
+            release("v2.1.0", true),
+            release("v2.0.2-rc1", false),
+            release("v2.0.1", false),
+            release("v2.0.0", false),
+            release("nightly", false),
+            release("v1.9.0", false),
+        ]);
+
+        let find = async |req: &str| {
+            latest_in_range(&source, "owner", "repo", &VersionReq::parse(req).unwrap())
+                .await
+                .unwrap()
+                .map(|release| release.tag_name)
+        };
+
+        assert_eq!(find("~2.0").await.as_deref(), Some("v2.0.1"));
+        assert_eq!(find("^1").await.as_deref(), Some("v1.9.0"));
+        assert_eq!(find(">=2.0.2-rc1").await.as_deref(), Some("v2.0.2-rc1"));
+        assert_eq!(find("^3").await, None);
+    }
+
+    #[tokio::test]
+    async fn test_recent_releases() {
+        let source = StaticSource(vec![
+            release("v2.1.0", true),
+            release("v2.0.2-rc1", false),
+            release("v2.0.1", false),
+            release("v2.0.0", false),
+            release("nightly", false),
+            release("v1.9.0", false),
+        ]);
+
+        let recent = async |count| {
+            recent_releases(&source, "owner", "repo", count)
+                .await
+                .unwrap()
+                .into_iter()
+                .map(|release| release.tag_name)
+                .collect::<Vec<_>>()
+        };
+
+        // Drafts and pre-releases don't count
+        assert_eq!(recent(2).await, ["v2.0.1", "v2.0.0"]);
+        assert_eq!(recent(4).await, ["v2.0.1", "v2.0.0", "nightly", "v1.9.0"]);
+        assert_eq!(recent(10).await.len(), 4);
+
+        // Stable releases behind many pre-releases
+        let mut releases: Vec<_> = (0..30)
+            .map(|i| release(&format!("v3.0.0-rc{i}"), false))
+            .collect();
+        releases.extend([release("v2.0.0", false), release("v1.0.0", false)]);
+        let source = StaticSource(releases);
+
+        let recent = recent_releases(&source, "owner", "repo", 2).await.unwrap();
+        let tags: Vec<_> = recent.iter().map(|r| r.tag_name.as_str()).collect();
+        assert_eq!(tags, ["v2.0.0", "v1.0.0"]);
+    }
 }
@@ -0,0 +1,329 @@
+//! Responses with the files of the repositories.
+//!
+//! Files are tagged with their SHA256, so that clients can revalidate their copy with a
+//! conditional request (`If-None-Match`, `If-Modified-Since`) and resume an interrupted
+//! download with a `Range` request.
+use std::{io::SeekFrom, ops::Bound, path::Path, time::SystemTime};
+
+use anyhow::Result;
+use axum::{
+    body::Body,
+    http::{
+        HeaderMap, StatusCode,
+        header::{ACCEPT_RANGES, CONTENT_LENGTH, CONTENT_RANGE, CONTENT_TYPE},
+    },
+    response::{IntoResponse, Response},
+};
+use axum_extra::headers::{
+    ETag, HeaderMapExt, IfModifiedSince, IfNoneMatch, IfRange, LastModified, Range,
+};
+use chrono::{DateTime, Utc};
+use sha2::Sha256;
+use tokio::io::{AsyncReadExt, AsyncSeekExt};
+use tokio_util::io::ReaderStream;
+
+use crate::utils::hashsum;
+
+/// Part of a file selected by the headers of a request.
+#[derive(Debug, PartialEq)]
+enum Selection {
+    /// The client's copy is up to date.
+    NotModified,
+    /// The whole file.
+    Full,
+    /// The bytes from the first to the last one, inclusive.
+    Partial(u64, u64),
+    /// None of the requested ranges is in the file.
+    Unsatisfiable,
+}
+
+/// Validators of a file, which identify its content.
+struct Validators {
+    etag: ETag,
+    last_modified: Option<LastModified>,
+}
+
+impl Validators {
+    fn new(sha256: &str, last_modified: Option<SystemTime>) -> Self {
+        Self {
+            etag: format!("\"{sha256}\"").parse().unwrap(),
+            last_modified: last_modified.map(LastModified::from),
+        }
+    }
+
+    /// Select the part of the `len` bytes long file requested with the `headers`.
+    fn select(&self, headers: &HeaderMap, len: u64) -> Selection {
+        // `If-Modified-Since` is ignored along with `If-None-Match`.
+        let not_modified = match headers.typed_get::<IfNoneMatch>() {
+            Some(if_none_match) => !if_none_match.precondition_passes(&self.etag),
+            None => headers
+                .typed_get::<IfModifiedSince>()
+                .zip(self.last_modified)
+                .is_some_and(|(since, modified)| !since.is_modified(modified.into())),
+        };
+
+        if not_modified {
+            return Selection::NotModified;
+        }
+
+        let Some(range) = headers.typed_get::<Range>() else {
+            return Selection::Full;
+        };
+
+        // A range of another version of the file is answered with the whole file.
+        let outdated = headers.typed_get::<IfRange>().is_some_and(|if_range| {
+            if_range.is_modified(Some(&self.etag), self.last_modified.as_ref())
+        });
+
+        if outdated {
+            return Selection::Full;
+        }
+
+        let ranges: Vec<_> = range.satisfiable_ranges(len).collect();
+
+        match ranges[..] {
+            [range] => match byte_range(range, len) {
+                Some((start, end)) => Selection::Partial(start, end),
+                None => Selection::Unsatisfiable,
+            },
+            [] => Selection::Unsatisfiable,
+            // Multiple ranges are answered with the whole file.
+            _ => Selection::Full,
+        }
+    }
+
+    /// Response of the `selection` of a `len` bytes long file, without its content.
+    fn response(&self, selection: &Selection, len: u64) -> (StatusCode, HeaderMap) {
+        let mut headers = HeaderMap::new();
+        headers.typed_insert(self.etag.clone());
+        if let Some(last_modified) = self.last_modified {
+            headers.typed_insert(last_modified);
+        }
+
+        let status = match *selection {
+            Selection::NotModified => return (StatusCode::NOT_MODIFIED, headers),
+            Selection::Full => StatusCode::OK,
+            Selection::Partial(start, end) => {
+                headers.insert(
+                    CONTENT_RANGE,
+                    format!("bytes {start}-{end}/{len}").parse().unwrap(),
+                );
+                StatusCode::PARTIAL_CONTENT
+            }
+            Selection::Unsatisfiable => {
+                headers.insert(CONTENT_RANGE, format!("bytes */{len}").parse().unwrap());
+                return (StatusCode::RANGE_NOT_SATISFIABLE, headers);
+            }
+        };
+
+        headers.insert(ACCEPT_RANGES, "bytes".parse().unwrap());
+        (status, headers)
+    }
+}
+
+/// Respond with the `data` of a file, like an index of a repository, last modified at
+/// `last_modified`.
+pub fn data_response(
+    data: Vec<u8>,
+    last_modified: Option<DateTime<Utc>>,
+    headers: &HeaderMap,
+) -> Response {
+    let validators = Validators::new(&hashsum::<Sha256>(&data), last_modified.map(Into::into));
+    let len = data.len() as u64;
+
+    let selection = validators.select(headers, len);
+    let (status, headers) = validators.response(&selection, len);
+
+    match selection {
+        Selection::Full => (status, headers, data).into_response(),
+        Selection::Partial(start, end) => (
+            status,
+            headers,
+            data[start as usize..=end as usize].to_vec(),
+        )
+            .into_response(),
+        Selection::NotModified | Selection::Unsatisfiable => (status, headers).into_response(),
+    }
+}
+
+/// Respond with the file at `path`, whose content has the `sha256`.
+pub async fn file_response(path: &Path, sha256: &str, headers: &HeaderMap) -> Result<Response> {
+    let validators = Validators::new(sha256, None);
+
+    let mut file = tokio::fs::File::open(path).await?;
+    let len = file.metadata().await?.len();
+
+    let selection = validators.select(headers, len);
+    let (status, mut headers) = validators.response(&selection, len);
+
+    let (start, length) = match selection {
+        Selection::Full => (0, len),
+        Selection::Partial(start, end) => (start, end - start + 1),
+        Selection::NotModified | Selection::Unsatisfiable => {
+            return Ok((status, headers).into_response());
+        }
+    };
+
+    file.seek(SeekFrom::Start(start)).await?;
+    let body = Body::from_stream(ReaderStream::new(file.take(length)));
+
+    headers.insert(CONTENT_TYPE, "application/octet-stream".parse()?);
+    headers.insert(CONTENT_LENGTH, length.into());
+
+    Ok((status, headers, body).into_response())
+}
+
+/// First and last byte of the `range` of a `len` bytes long content, if it's satisfiable.
+fn byte_range((start, end): (Bound<u64>, Bound<u64>), len: u64) -> Option<(u64, u64)> {
+    let last = len.checked_sub(1)?;
+
+    let start = match start {
+        Bound::Included(start) => start,
+        Bound::Excluded(start) => start + 1,
+        Bound::Unbounded => 0,
+    };
+    let end = match end {
+        Bound::Included(end) => end.min(last),
+        Bound::Excluded(end) => end.checked_sub(1)?.min(last),
+        Bound::Unbounded => last,
+    };
+
+    (start <= end).then_some((start, end))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use axum::{
+        body::to_bytes,
+        http::{
+            HeaderName,
+            header::{ETAG, IF_MODIFIED_SINCE, IF_NONE_MATCH, IF_RANGE, LAST_MODIFIED, RANGE},
+        },
+    };
+    use chrono::TimeZone;
+
+    use super::*;
+
+    async fn parts(res: Response) -> (StatusCode, HeaderMap, Vec<u8>) {
+        let status = res.status();
+        let headers = res.headers().clone();
+        let body = to_bytes(res.into_body(), usize::MAX).await.unwrap();
+        (status, headers, body.to_vec())
+    }
+
+    fn headers(headers: &[(HeaderName, &str)]) -> HeaderMap {
+        headers
+            .iter()
+            .map(|(name, value)| (name.clone(), value.parse().unwrap()))
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn test_file_response() {
+        let dir = tempfile::tempdir().unwrap();
+        let data: Vec<u8> = (0..=255).collect();
+        let sha256 = hashsum::<Sha256>(&data);
+        let path = dir.path().join(&sha256);
+        fs::write(&path, &data).unwrap();
+
+        let response = async |request: &[(HeaderName, &str)]| {
+            parts(
+                file_response(&path, &sha256, &headers(request))
+                    .await
+                    .unwrap(),
+            )
+            .await
+        };
+
+        let (status, headers, body) = response(&[]).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(headers[ETAG], format!("\"{sha256}\""));
+        assert_eq!(headers[ACCEPT_RANGES], "bytes");
+        assert_eq!(headers[CONTENT_LENGTH], "256");
+        assert_eq!(body, data);
+
+        let etag = format!("\"{sha256}\"");
+        let (status, _, body) = response(&[(IF_NONE_MATCH, &etag)]).await;
+        assert_eq!(status, StatusCode::NOT_MODIFIED);
+        assert!(body.is_empty());
+
+        let (status, headers, body) = response(&[(RANGE, "bytes=10-19")]).await;
+        assert_eq!(status, StatusCode::PARTIAL_CONTENT);
+        assert_eq!(headers[CONTENT_RANGE], "bytes 10-19/256");
+        assert_eq!(headers[CONTENT_LENGTH], "10");
+        assert_eq!(body, data[10..20]);
+
+        let (status, headers, body) = response(&[(RANGE, "bytes=250-")]).await;
+        assert_eq!(status, StatusCode::PARTIAL_CONTENT);
+        assert_eq!(headers[CONTENT_RANGE], "bytes 250-255/256");
+        assert_eq!(body, data[250..]);
+
+        let (status, _, body) = response(&[(RANGE, "bytes=-6")]).await;
+        assert_eq!(status, StatusCode::PARTIAL_CONTENT);
+        assert_eq!(body, data[250..]);
+
+        let (status, headers, _) = response(&[(RANGE, "bytes=300-")]).await;
+        assert_eq!(status, StatusCode::RANGE_NOT_SATISFIABLE);
+        assert_eq!(headers[CONTENT_RANGE], "bytes */256");
+
+        // The range of another version of the file
+        let (status, _, body) = response(&[(RANGE, "bytes=10-19"), (IF_RANGE, "\"other\"")]).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body, data);
+
+        let (status, _, body) = response(&[(RANGE, "bytes=10-19"), (IF_RANGE, &etag)]).await;
+        assert_eq!(status, StatusCode::PARTIAL_CONTENT);
+        assert_eq!(body, data[10..20]);
+    }
+
+    #[tokio::test]
+    async fn test_data_response() {
+        let data = b"Package: tool\n".to_vec();
+        let modified = Utc.with_ymd_and_hms(2024, 7, 1, 12, 0, 0).unwrap();
+
+        let response = async |request: &[(HeaderName, &str)]| {
+            parts(data_response(
+                data.clone(),
+                Some(modified),
+                &headers(request),
+            ))
+            .await
+        };
+
+        let (status, headers, body) = response(&[]).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(headers[LAST_MODIFIED], "Mon, 01 Jul 2024 12:00:00 GMT");
+        assert_eq!(body, data);
+        let etag = headers[ETAG].to_str().unwrap().to_owned();
+
+        let (status, _, body) = response(&[(IF_NONE_MATCH, &etag)]).await;
+        assert_eq!(status, StatusCode::NOT_MODIFIED);
+        assert!(body.is_empty());
+
+        let (status, _, _) = response(&[(IF_NONE_MATCH, "\"other\"")]).await;
+        assert_eq!(status, StatusCode::OK);
+
+        let (status, _, _) =
+            response(&[(IF_MODIFIED_SINCE, "Mon, 01 Jul 2024 12:00:00 GMT")]).await;
+        assert_eq!(status, StatusCode::NOT_MODIFIED);
+
+        let (status, _, _) =
+            response(&[(IF_MODIFIED_SINCE, "Sun, 30 Jun 2024 12:00:00 GMT")]).await;
+        assert_eq!(status, StatusCode::OK);
+
+        // `If-None-Match` takes precedence over `If-Modified-Since`
+        let (status, _, _) = response(&[
+            (IF_NONE_MATCH, "\"other\""),
+            (IF_MODIFIED_SINCE, "Mon, 01 Jul 2024 12:00:00 GMT"),
+        ])
+        .await;
+        assert_eq!(status, StatusCode::OK);
+
+        let (status, headers, body) = response(&[(RANGE, "bytes=0-7")]).await;
+        assert_eq!(status, StatusCode::PARTIAL_CONTENT);
+        assert_eq!(headers[CONTENT_RANGE], "bytes 0-7/14");
+        assert_eq!(body, b"Package:");
+    }
+}
@@ -1,8 +1,10 @@
+use std::collections::BTreeMap;
+
 use askama::Template;
 use sha2::Sha256;
 use zstd::encode_all;
 
-use crate::utils::hashsum;
+use crate::utils::{Arch, hashsum};
 
 use super::package::RPMPackage;
 
@@ -55,7 +57,10 @@ pub fn get_other_index(packages: &[RPMPackage]) -> String {
     list.render().unwrap()
 }
 
-pub fn get_repomd_index(packages: &[RPMPackage]) -> String {
+/// Render the repository metadata files compressed with zstd, along with the `repomd.xml` index listing them.
+///
+/// The files are returned with their names.
+pub fn get_repodata(packages: &[RPMPackage]) -> (String, Vec<(String, Vec<u8>)>) {
     let primary = get_primary_index(packages);
     let filelists = get_filelists_index(packages);
     let other = get_other_index(packages);
@@ -68,41 +73,67 @@ pub fn get_repomd_index(packages: &[RPMPackage]) -> String {
         }
     }
 
-    let repomd = RepoMD::create(primary, filelists, other, timestamp);
+    let primary_zst = encode_all(primary.as_bytes(), 0).unwrap();
+    let filelists_zst = encode_all(filelists.as_bytes(), 0).unwrap();
+    let other_zst = encode_all(other.as_bytes(), 0).unwrap();
+
+    let repomd = RepoMD {
+        primary: Metadata::create(&primary, &primary_zst),
+        filelists: Metadata::create(&filelists, &filelists_zst),
+        other: Metadata::create(&other, &other_zst),
+        timestamp,
+    };
+
+    let files = vec![
+        ("primary.xml.zst".to_owned(), primary_zst),
+        ("filelists.xml.zst".to_owned(), filelists_zst),
+        ("other.xml.zst".to_owned(), other_zst),
+    ];
 
-    repomd.render().unwrap()
+    (repomd.render().unwrap(), files)
 }
 
-impl Metadata {
-    /// Create the metadata of the `content`.
-    fn create(content: String) -> Metadata {
-        let data = content.as_bytes();
-        let open_size = data.len();
-        let open_sha256 = hashsum::<Sha256>(data);
-        let compressed = encode_all(data, 0).unwrap();
-        let size = compressed.len();
-        let sha256 = hashsum::<Sha256>(&compressed);
+/// Split the packages into the repositories of each architecture, keyed by the RPM name
+/// of the architecture (the `$basearch` of the clients).
+///
+/// Architecture independent packages are part of every repository. They also make up
+/// the `noarch` repository, which is served to the architectures without packages.
+pub fn split_by_arch(packages: &[RPMPackage]) -> BTreeMap<String, Vec<RPMPackage>> {
+    let (noarch, packages): (Vec<_>, Vec<_>) = packages
+        .iter()
+        .partition(|package| rpm_arch(&package.arch) == Arch::Noarch.rpm_name());
 
-        Metadata {
-            sha256,
-            open_sha256,
-            size,
-            open_size,
-        }
+    let mut repositories: BTreeMap<String, Vec<RPMPackage>> = BTreeMap::new();
+    repositories.insert(Arch::Noarch.rpm_name().to_owned(), Vec::new());
+
+    for package in packages {
+        repositories
+            .entry(rpm_arch(&package.arch))
+            .or_default()
+            .push(package.clone());
     }
+
+    for repository in repositories.values_mut() {
+        repository.extend(noarch.iter().copied().cloned());
+    }
+
+    repositories
 }
 
-impl RepoMD {
-    fn create(primary: String, filelists: String, other: String, timestamp: i64) -> RepoMD {
-        let primary = Metadata::create(primary);
-        let filelists = Metadata::create(filelists);
-        let other = Metadata::create(other);
-
-        RepoMD {
-            primary,
-            filelists,
-            other,
-            timestamp,
+/// The RPM name of the architecture `arch`, which is kept as is when it's unknown.
+pub fn rpm_arch(arch: &str) -> String {
+    arch.parse::<Arch>()
+        .map_or_else(|_| arch.to_owned(), |arch| arch.rpm_name().to_owned())
+}
+
+impl Metadata {
+    /// Create the metadata of the `content` and its `compressed` form.
+    fn create(content: &str, compressed: &[u8]) -> Metadata {
+        Metadata {
+            sha256: hashsum::<Sha256>(compressed),
+            open_sha256: hashsum::<Sha256>(content.as_bytes()),
+            size: compressed.len(),
+            open_size: content.len(),
         }
     }
 }
@@ -131,7 +162,7 @@ mod tests {
 
         assert_snapshot!(get_other_index(&packages));
 
-        assert_snapshot!(get_repomd_index(&packages));
+        assert_snapshot!(get_repodata(&packages).0);
     }
 
     #[test]
@@ -178,6 +209,44 @@ mod tests {
         assert_snapshot!(get_primary_index(&packages));
         assert_snapshot!(get_filelists_index(&packages));
         assert_snapshot!(get_other_index(&packages));
-        assert_snapshot!(get_repomd_index(&packages));
+        assert_snapshot!(get_repodata(&packages).0);
+    }
+
+    #[test]
+    fn test_split_by_arch() {
+        let package = package_with_ver("fastfetch-linux-aarch64.rpm", "2.40.3");
+        package.set_package_data(read("data/fastfetch-linux-aarch64.rpm").unwrap());
+        let aarch64 = RPMPackage::from_package(&package).unwrap();
+
+        let package = package_with_ver("fastfetch-linux-amd64.rpm", "2.40.3");
+        package.set_package_data(read("data/fastfetch-linux-amd64.rpm").unwrap());
+        let x86_64 = RPMPackage::from_package(&package).unwrap();
+
+        let mut noarch = x86_64.clone();
+        noarch.name = "fastfetch-data".to_owned();
+        noarch.arch = "noarch".to_owned();
+
+        let repositories = split_by_arch(&[aarch64, x86_64, noarch]);
+
+        let names = |arch: &str| {
+            repositories[arch]
+                .iter()
+                .map(|package| format!("{}.{}", package.name, package.arch))
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(
+            repositories.keys().collect::<Vec<_>>(),
+            ["aarch64", "noarch", "x86_64"]
+        );
+        assert_eq!(
+            names("x86_64"),
+            ["fastfetch.x86_64", "fastfetch-data.noarch"]
+        );
+        assert_eq!(
+            names("aarch64"),
+            ["fastfetch.aarch64", "fastfetch-data.noarch"]
+        );
+        assert_eq!(names("noarch"), ["fastfetch-data.noarch"]);
     }
 }
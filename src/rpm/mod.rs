@@ -2,4 +2,7 @@ mod index;
 mod package;
 mod routes;
 
-pub use self::routes::{rpm_routes_v1, rpm_routes_v2};
+pub use self::{
+    package::RPMPackage,
+    routes::{rpm_routes_v1, rpm_routes_v2},
+};
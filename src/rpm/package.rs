@@ -2,12 +2,8 @@ use anyhow::{Context, Result, bail};
 use rpm::{DependencyFlags, FileMode, IndexSignatureTag};
 use serde::{Deserialize, Serialize};
 use serde_json::{from_str, to_string};
-use sha2::Sha256;
 
-use crate::{
-    package::{Data, Package},
-    utils::hashsum,
-};
+use crate::package::{Data, Package};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct RPMPackage {
@@ -63,18 +59,16 @@ impl RPMPackage {
             return Ok(rpm);
         }
 
-        let Data::Package(data) = package.data() else {
+        let Data::Package(file) = package.data() else {
             bail!("Data isn't loaded in package");
         };
 
-        let mut data = data.as_slice();
-        // Calculate these before the data slice is mutated
-        let pkg_size = data.len();
-        let sha256 = hashsum::<Sha256>(data);
+        let pkg_size = file.size() as usize;
+        let sha256 = file.sha256().to_owned();
 
-        let rpm = rpm::Package::parse(&mut data)
+        // Only the lead, signature and header are read, the payload is left on disk.
+        let header = rpm::PackageMetadata::parse(&mut file.open()?)
             .context("Unable to parse the package using rpm parser crate")?;
-        let header = rpm.metadata;
 
         let name = header.get_name()?.to_owned();
         let epoch = header.get_epoch().unwrap_or(0);
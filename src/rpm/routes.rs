@@ -1,143 +1,244 @@
-use anyhow::{Context, anyhow};
+use anyhow::Context;
 use axum::{
     Router,
-    body::Body,
     extract::{Path, State},
-    response::IntoResponse,
+    http::{HeaderMap, Method},
+    response::Response,
     routing::get,
 };
 use axum_extra::{headers::UserAgent, typed_header::TypedHeader};
-use zstd::encode_all;
+use chrono::{DateTime, Utc};
+use serde::Deserialize;
 
 use crate::{
-    REQWEST,
-    error::AppError,
+    error::{AppError, PackhubError},
+    proxy::{package_response, release_asset},
     repository::Repository,
-    rpm::{index::get_repomd_index, package::RPMPackage},
+    response::data_response,
+    rpm::{
+        index::{get_repodata, rpm_arch, split_by_arch},
+        package::RPMPackage,
+    },
+    source::Forge,
     state::AppState,
-    utils::ReleaseChannel,
+    utils::{Arch, ReleaseChannel},
 };
 
-use super::index::{get_filelists_index, get_other_index, get_primary_index};
-
+/// Serve the index `file` of the repository.
+///
+/// The `release` is the os-release `ID` and the `$releasever` of the client given in the
+/// repository path, otherwise the distribution is detected from the user-agent. With a
+/// `basearch`, only the packages of the architecture and the architecture independent
+/// ones are listed.
+///
+/// The content of the file is returned along with when it last changed, if it's known.
 async fn handle_repo_index(
     state: &AppState,
-    owner: &str,
-    repo: &str,
+    (forge, owner, repo): (Forge, &str, &str),
     file: &str,
     channel: &ReleaseChannel,
+    release: Option<(&str, &str)>,
+    basearch: Option<&str>,
     agent: &UserAgent,
-) -> Result<Vec<u8>, AppError> {
-    let mut repo = Repository::from_github(owner, repo, &channel, &state).await;
-    let packages: Vec<RPMPackage> = repo
-        .select_package_rpm(agent.as_str())
-        .await?
-        .into_iter()
-        .map(|p| {
-            RPMPackage::from_package(&p).context(format!(
-                "Error while parsing package into RPMPackage: {p:?}"
-            ))
+) -> Result<(Vec<u8>, Option<DateTime<Utc>>), AppError> {
+    if file == "repomd.xml.key" {
+        return Ok((state.armored_public_key(), None));
+    }
+
+    let project = format!("{owner}/{repo}");
+    let mut repo = Repository::from_forge(forge, owner, repo, channel, state).await?;
+    let dist = repo.detect_rpm_dist(release, agent.as_str())?;
+
+    let snapshot = repo
+        .snapshot(dist, |packages| {
+            let packages: Vec<RPMPackage> = packages
+                .iter()
+                .map(|p| {
+                    RPMPackage::from_package(p)
+                        .with_context(|| PackhubError::ParseFailure(p.file_name().to_owned()))
+                })
+                .collect::<Result<Vec<_>, _>>()?;
+
+            let mut files = Vec::new();
+
+            // The repository of every architecture, along with the ones of each architecture.
+            let repositories = split_by_arch(&packages)
+                .into_iter()
+                .map(|(arch, packages)| (format!("{arch}/"), packages));
+
+            for (prefix, packages) in [(String::new(), packages)].into_iter().chain(repositories) {
+                let (repomd, repodata) = get_repodata(&packages);
+
+                let signature = state.detached_sign_metadata(&repomd)?;
+
+                for (name, data) in repodata {
+                    files.push((format!("{prefix}{name}"), data));
+                }
+                files.push((format!("{prefix}repomd.xml.asc"), signature));
+                files.push((format!("{prefix}repomd.xml"), repomd.into_bytes()));
+            }
+
+            Ok(files)
         })
-        .collect::<Result<Vec<_>, _>>()?;
+        .await?;
+
+    let mut path = file.to_owned();
 
-    repo.save_package_metadata().await;
+    if let Some(arch) = basearch.map(rpm_arch) {
+        if snapshot.file(&format!("{arch}/repomd.xml")).is_some() {
+            path = format!("{arch}/{file}");
+        } else {
+            tracing::info!(
+                "No packages of {project} for the architecture {arch}, serving the noarch packages"
+            );
 
-    match file {
-        "repomd.xml" => Ok(get_repomd_index(&packages).into_bytes()),
-        "repomd.xml.asc" => {
-            let metadata = get_repomd_index(&packages);
-            let signature = state.detached_sign_metadata(&metadata)?;
-            Ok(signature)
+            path = format!("{}/{file}", Arch::Noarch.rpm_name());
         }
-        "repomd.xml.key" => Ok(state.armored_public_key()),
-        "primary.xml.zst" => Ok(encode_all(get_primary_index(&packages).as_bytes(), 0)?),
-        "filelists.xml.zst" => Ok(encode_all(get_filelists_index(&packages).as_bytes(), 0)?),
-        "other.xml.zst" => Ok(encode_all(get_other_index(&packages).as_bytes(), 0)?),
-        file => Err(anyhow!("Unknown file requested: {file}").into()),
     }
-}
 
-async fn handle_repo_package(
-    owner: &str,
-    repo: &str,
-    ver: &str,
-    file: &str,
-) -> Result<impl IntoResponse + use<>, AppError> {
-    let url = format!("https://github.com/{owner}/{repo}/releases/download/{ver}/{file}");
-    let res = REQWEST
-        .get(url)
-        .send()
-        .await
-        .context("Error occurred while proxying package")?;
-    let stream = res.bytes_stream();
-    let stream = Body::from_stream(stream);
-
-    Ok(stream)
+    let data = snapshot
+        .file(&path)
+        .with_context(|| PackhubError::NotFound(format!("Unknown file requested: {file}")))?;
+
+    Ok((data.to_owned(), snapshot.last_modified()))
 }
 
 #[tracing::instrument(name = "RPM Index V1", skip_all, fields(agent = agent.as_str()))]
 async fn index_v1(
     State(state): State<AppState>,
-    Path((owner, repo, file)): Path<(String, String, String)>,
+    Path((forge, owner, repo, file)): Path<(Forge, String, String, String)>,
     TypedHeader(agent): TypedHeader<UserAgent>,
-) -> Result<Vec<u8>, AppError> {
-    handle_repo_index(
+    headers: HeaderMap,
+) -> Result<Response, AppError> {
+    let (data, last_modified) = handle_repo_index(
         &state,
-        &owner,
-        &repo,
+        (forge, &owner, &repo),
         &file,
         &ReleaseChannel::Stable,
+        None,
+        None,
         &agent,
     )
-    .await
+    .await?;
+
+    Ok(data_response(data, last_modified, &headers))
+}
+
+/// Path of an index file of a repository.
+#[derive(Deserialize)]
+struct IndexPath {
+    forge: Forge,
+    owner: String,
+    repo: String,
+    channel: ReleaseChannel,
+    /// The os-release `ID` of the client.
+    distro: Option<String>,
+    releasever: Option<String>,
+    basearch: Option<String>,
+    file: String,
 }
 
 #[tracing::instrument(name = "RPM Index V2", skip_all, fields(agent = agent.as_str()))]
 async fn index_v2(
     State(state): State<AppState>,
-    Path((owner, repo, channel, file)): Path<(String, String, ReleaseChannel, String)>,
+    Path(path): Path<IndexPath>,
     TypedHeader(agent): TypedHeader<UserAgent>,
-) -> Result<Vec<u8>, AppError> {
-    handle_repo_index(&state, &owner, &repo, &file, &channel, &agent).await
+    headers: HeaderMap,
+) -> Result<Response, AppError> {
+    let release = path.distro.as_deref().zip(path.releasever.as_deref());
+
+    let (data, last_modified) = handle_repo_index(
+        &state,
+        (path.forge, &path.owner, &path.repo),
+        &path.file,
+        &path.channel,
+        release,
+        path.basearch.as_deref(),
+        &agent,
+    )
+    .await?;
+
+    Ok(data_response(data, last_modified, &headers))
 }
 
-#[tracing::instrument(name = "RPM Package proxy", skip_all)]
-async fn package_v1(
-    Path((owner, repo, ver, file)): Path<(String, String, String, String)>,
-) -> Result<impl IntoResponse, AppError> {
-    handle_repo_package(&owner, &repo, &ver, &file).await
+/// Path of a package in a repository.
+///
+/// The package is proxied the same whether the repository is for a release of a
+/// distribution or for an architecture.
+#[derive(Deserialize)]
+struct PackagePath {
+    forge: Forge,
+    owner: String,
+    repo: String,
+    /// The tag of the release, which can contain `/`, and the file name of the package.
+    path: String,
 }
 
-#[tracing::instrument(name = "RPM Package proxy V2", skip_all)]
-async fn package_v2(
-    Path((owner, repo, _channel, ver, file)): Path<(
-        String,
-        String,
-        ReleaseChannel,
-        String,
-        String,
-    )>,
-) -> Result<impl IntoResponse, AppError> {
-    handle_repo_package(&owner, &repo, &ver, &file).await
+#[tracing::instrument(name = "RPM Package proxy", skip_all)]
+async fn package(
+    State(state): State<AppState>,
+    Path(PackagePath {
+        forge,
+        owner,
+        repo,
+        path,
+    }): Path<PackagePath>,
+    method: Method,
+    headers: HeaderMap,
+) -> Result<Response, AppError> {
+    let (tag, file) = release_asset(&path)?;
+
+    package_response(
+        &state,
+        (forge, &owner, &repo),
+        tag,
+        file,
+        (&method, &headers),
+    )
+    .await
 }
 
 pub fn rpm_routes_v1() -> Router<AppState> {
     Router::new()
-        .route("/github/{owner}/{repo}/repodata/{file}", get(index_v1))
-        .route(
-            "/github/{owner}/{repo}/package/{ver}/{file}",
-            get(package_v1),
-        )
+        .route("/{forge}/{owner}/{repo}/repodata/{file}", get(index_v1))
+        .route("/{forge}/{owner}/{repo}/package/{*path}", get(package))
 }
 
 pub fn rpm_routes_v2() -> Router<AppState> {
     Router::new()
         .route(
-            "/github/{owner}/{repo}/{channel}/repodata/{file}",
+            "/{forge}/{owner}/{repo}/{channel}/repodata/{file}",
+            get(index_v2),
+        )
+        .route(
+            "/{forge}/{owner}/{repo}/{channel}/package/{*path}",
+            get(package),
+        )
+        // Repositories of an architecture, the `$basearch` of the client.
+        .route(
+            "/{forge}/{owner}/{repo}/{channel}/{basearch}/repodata/{file}",
+            get(index_v2),
+        )
+        .route(
+            "/{forge}/{owner}/{repo}/{channel}/{basearch}/package/{*path}",
+            get(package),
+        )
+        // The distribution (os-release `ID`) and `$releasever` in the path override
+        // the detection from the user-agent.
+        .route(
+            "/{forge}/{owner}/{repo}/{channel}/{distro}/{releasever}/repodata/{file}",
+            get(index_v2),
+        )
+        .route(
+            "/{forge}/{owner}/{repo}/{channel}/{distro}/{releasever}/package/{*path}",
+            get(package),
+        )
+        .route(
+            "/{forge}/{owner}/{repo}/{channel}/{distro}/{releasever}/{basearch}/repodata/{file}",
             get(index_v2),
         )
         .route(
-            "/github/{owner}/{repo}/{channel}/package/{ver}/{file}",
-            get(package_v2),
+            "/{forge}/{owner}/{repo}/{channel}/{distro}/{releasever}/{basearch}/package/{*path}",
+            get(package),
         )
 }
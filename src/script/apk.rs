@@ -0,0 +1,48 @@
+use crate::{source::Forge, utils::ReleaseChannel};
+use askama::Template;
+
+#[derive(Template)]
+#[template(path = "apk-script.sh", escape = "none")]
+pub(crate) struct ApkScript<'a> {
+    pub(crate) host: &'a str,
+    pub(crate) forge: &'a Forge,
+    pub(crate) owner: &'a str,
+    pub(crate) repo: &'a str,
+    pub(crate) channel: &'a ReleaseChannel,
+}
+
+pub(crate) fn generate_apk_script(
+    host: &str,
+    forge: &Forge,
+    owner: &str,
+    repo: &str,
+    channel: &ReleaseChannel,
+) -> String {
+    let script = ApkScript {
+        host,
+        forge,
+        owner,
+        repo,
+        channel,
+    };
+    script.render().unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use insta::assert_snapshot;
+
+    use super::*;
+
+    #[test]
+    fn test_script_generation_apk() {
+        let apk_script = generate_apk_script(
+            "http://localhost:3000",
+            &Forge::Github,
+            "OpenBangla",
+            "OpenBangla-Keyboard",
+            &ReleaseChannel::Stable,
+        );
+        assert_snapshot!(apk_script);
+    }
+}
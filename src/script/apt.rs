@@ -1,4 +1,4 @@
-use crate::utils::ReleaseChannel;
+use crate::{source::Forge, utils::ReleaseChannel};
 use askama::Template;
 
 #[derive(Template)]
@@ -6,21 +6,24 @@ use askama::Template;
 pub(crate) struct AptScript<'a> {
     pub(crate) host: &'a str,
     pub(crate) distro: &'a str,
+    pub(crate) forge: &'a Forge,
     pub(crate) owner: &'a str,
     pub(crate) repo: &'a str,
     pub(crate) channel: &'a ReleaseChannel,
 }
 
 pub(crate) fn generate_apt_script(
+    host: &str,
     distro: &str,
+    forge: &Forge,
     owner: &str,
     repo: &str,
     channel: &ReleaseChannel,
 ) -> String {
-    let host = dotenvy::var("PACKHUB_DOMAIN").unwrap();
     let script = AptScript {
-        host: &host,
+        host,
         distro,
+        forge,
         owner,
         repo,
         channel,
@@ -37,7 +40,9 @@ mod tests {
     #[test]
     fn test_script_generation_apt() {
         let apt_script = generate_apt_script(
+            "http://localhost:3000",
             "ubuntu",
+            &Forge::Github,
             "OpenBangla",
             "OpenBangla-Keyboard",
             &ReleaseChannel::Stable,
@@ -45,7 +50,9 @@ mod tests {
         assert_snapshot!(apt_script);
 
         let apt_script_unstable = generate_apt_script(
+            "http://localhost:3000",
             "ubuntu",
+            &Forge::Github,
             "OpenBangla",
             "OpenBangla-Keyboard",
             &ReleaseChannel::Unstable,
@@ -1,32 +1,97 @@
-use anyhow::anyhow;
 use axum::{
     Router,
-    extract::{Path, Query},
+    extract::{Path, Query, State},
     routing::get,
 };
 use serde::Deserialize;
 
 use crate::{
-    error::AppError,
+    error::{AppError, PackhubError},
+    source::Forge,
     state::AppState,
     utils::{AppVersion, ReleaseChannel},
 };
 
+mod apk;
 mod apt;
+mod pacman;
 mod rpm;
 
 #[derive(Deserialize)]
 struct ScriptParams {
     prerelease: Option<bool>,
+    /// Pin the repository to a release channel: a release tag, a version requirement
+    /// (e.g. `~2.0`) or `all` for every recent release.
+    tag: Option<String>,
     ver: Option<AppVersion>,
 }
 
+/// Characters of version requirements allowed in release channels, besides the ones of
+/// names.
+const CHANNEL_CHARS: &[char] = &['+', '~', '^', '=', '<', '>', '*', ','];
+
+/// Check that a `value` written into the scripts has no character special to the shell,
+/// since the scripts are piped to `sudo sh`.
+///
+/// Names of owners, repositories and tags consist of ASCII alphanumerics, `.`, `_` and `-`,
+/// besides the `extra` characters.
+fn check_script_value(what: &str, value: &str, extra: &[char]) -> Result<(), PackhubError> {
+    let safe = |c: char| c.is_ascii_alphanumeric() || matches!(c, '.' | '_' | '-');
+
+    if value.is_empty() || !value.chars().all(|c| safe(c) || extra.contains(&c)) {
+        return Err(PackhubError::InvalidRequest(format!(
+            "Invalid {what}: {value}"
+        )));
+    }
+
+    Ok(())
+}
+
+/// Identifier of the repository of the `channel`, for the repository files and sections of
+/// the package managers.
+///
+/// Only the characters allowed in the ids of dnf repositories (`[A-Za-z0-9_.:-]`) are used,
+/// the ones of version requirements are spelled out.
+fn repo_id(repo: &str, channel: &ReleaseChannel) -> String {
+    if *channel == ReleaseChannel::Stable {
+        return repo.to_owned();
+    }
+
+    let mut id = format!("{repo}-");
+    for c in channel.to_string().chars() {
+        match c {
+            '+' => id.push_str("plus"),
+            '~' => id.push_str("tilde"),
+            '^' => id.push_str("caret"),
+            '=' => id.push_str("eq"),
+            '<' => id.push_str("lt"),
+            '>' => id.push_str("gt"),
+            '*' => id.push('x'),
+            c if c.is_ascii_alphanumeric() || matches!(c, '_' | '.' | ':' | '-') => id.push(c),
+            _ => id.push('_'),
+        }
+    }
+
+    id
+}
+
 async fn script_handler(
-    Path((distro, owner, repo)): Path<(String, String, String)>,
+    State(state): State<AppState>,
+    Path((distro, forge, owner, repo)): Path<(String, Forge, String, String)>,
     Query(params): Query<ScriptParams>,
 ) -> Result<String, AppError> {
+    let host = state.base_url();
     let ver = params.ver.unwrap_or(AppVersion::V2);
-    let channel = if let Some(prerelease) = params.prerelease
+    check_script_value("owner", &owner, &[])?;
+    check_script_value("repository", &repo, &[])?;
+
+    let channel = if let Some(tag) = params.tag {
+        let channel: ReleaseChannel = tag.parse().map_err(|e| {
+            PackhubError::InvalidRequest(format!("Invalid release channel {tag}: {e}"))
+        })?;
+        check_script_value("release channel", &channel.to_string(), CHANNEL_CHARS)?;
+        channel
+    } else if let Some(prerelease) = params.prerelease
         && prerelease
     {
         ReleaseChannel::Unstable
@@ -35,8 +100,12 @@ async fn script_handler(
     };
 
     match distro.as_str() {
-        "ubuntu" | "debian" => Ok(apt::generate_apt_script(&distro, &owner, &repo, &channel)),
+        "ubuntu" | "debian" => Ok(apt::generate_apt_script(
+            host, &distro, &forge, &owner, &repo, &channel,
+        )),
         "yum" => Ok(rpm::generate_rpm_script(
+            host,
+            &forge,
             &owner,
             &repo,
             "yum.repos.d",
@@ -44,18 +113,29 @@ async fn script_handler(
             &channel,
         )),
         "zypp" => Ok(rpm::generate_rpm_script(
+            host,
+            &forge,
             &owner,
             &repo,
             "zypp/repos.d",
             &ver,
             &channel,
         )),
-        _ => Err(anyhow!("Script Generation: Unsupported distro: {}", distro).into()),
+        "alpine" => Ok(apk::generate_apk_script(
+            host, &forge, &owner, &repo, &channel,
+        )),
+        "arch" | "archlinux" => Ok(pacman::generate_pacman_script(
+            host, &forge, &owner, &repo, &channel,
+        )),
+        _ => Err(PackhubError::UnsupportedClient(format!(
+            "Script Generation: Unsupported distro: {distro}"
+        ))
+        .into()),
     }
 }
 
 pub fn script_routes() -> Router<AppState> {
-    Router::new().route("/{distro}/github/{owner}/{repo}", get(script_handler))
+    Router::new().route("/{distro}/{forge}/{owner}/{repo}", get(script_handler))
 }
 
 #[cfg(test)]
@@ -84,6 +164,31 @@ mod tests {
             .text();
 
         assert_snapshot!(unstable);
+
+        let gitea = server
+            .get("/ubuntu/gitea/OpenBangla/OpenBangla-Keyboard")
+            .await
+            .text();
+
+        let pinned = server
+            .get("/ubuntu/github/OpenBangla/OpenBangla-Keyboard?tag=2.0.0")
+            .await
+            .text();
+
+        assert!(pinned.contains(
+            "/v1/apt/ubuntu${RELEASE:+/$RELEASE}/github/OpenBangla/OpenBangla-Keyboard 2.0.0 main"
+        ));
+
+        assert!(gitea.contains(
+            "/v1/apt/ubuntu${RELEASE:+/$RELEASE}/gitea/OpenBangla/OpenBangla-Keyboard stable main"
+        ));
+
+        let debian = server
+            .get("/debian/github/OpenBangla/OpenBangla-Keyboard")
+            .await
+            .text();
+
+        assert!(debian.contains("RELEASE=\"${DEBIAN_CODENAME:-$VERSION_CODENAME}\"\n"));
     }
 
     #[tokio::test]
@@ -147,5 +252,39 @@ mod tests {
             .text();
 
         assert_snapshot!(zypp_v1);
+
+        let yum_range = server
+            .get("/yum/github/OpenBangla/OpenBangla-Keyboard?tag=%3E%3D1.2%2C%3C2")
+            .await
+            .text();
+
+        assert!(yum_range.contains(
+            "sudo tee \"/etc/yum.repos.d/OpenBangla-Keyboard-gteq1.2_lt2.repo\" > /dev/null"
+        ));
+
+        for path in [
+            "/yum/github/OpenBangla/OpenBangla-Keyboard?tag=%24(reboot)",
+            "/yum/github/OpenBangla/OpenBangla-Keyboard?tag=v1%22%3Breboot",
+            "/yum/github/OpenBangla/OpenBangla-Keyboard?tag=%3E%3D1.2%2C%3C",
+            "/ubuntu/github/Open%24(reboot)/OpenBangla-Keyboard",
+        ] {
+            server
+                .get(path)
+                .expect_failure()
+                .await
+                .assert_status_bad_request();
+        }
+    }
+
+    #[test]
+    fn test_repo_id() {
+        let id = |channel: &str| repo_id("tool", &channel.parse().unwrap());
+
+        assert_eq!(id("stable"), "tool");
+        assert_eq!(id("unstable"), "tool-unstable");
+        assert_eq!(id("v2.0.0"), "tool-v2.0.0");
+        assert_eq!(id("~2.0"), "tool-tilde2.0");
+        assert_eq!(id(">=1.2,<2"), "tool-gteq1.2_lt2");
+        assert_eq!(id("^1"), "tool-caret1");
     }
 }
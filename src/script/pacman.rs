@@ -0,0 +1,64 @@
+use crate::{source::Forge, utils::ReleaseChannel};
+use askama::Template;
+
+#[derive(Template)]
+#[template(path = "pacman-script.sh", escape = "none")]
+pub(crate) struct PacmanScript<'a> {
+    pub(crate) host: &'a str,
+    pub(crate) forge: &'a Forge,
+    pub(crate) owner: &'a str,
+    pub(crate) repo: &'a str,
+    pub(crate) channel: &'a ReleaseChannel,
+}
+
+impl PacmanScript<'_> {
+    /// Name of the repository section in `pacman.conf`, which also names its databases.
+    pub(crate) fn repo_name(&self) -> String {
+        super::repo_id(self.repo, self.channel)
+    }
+}
+
+pub(crate) fn generate_pacman_script(
+    host: &str,
+    forge: &Forge,
+    owner: &str,
+    repo: &str,
+    channel: &ReleaseChannel,
+) -> String {
+    let script = PacmanScript {
+        host,
+        forge,
+        owner,
+        repo,
+        channel,
+    };
+    script.render().unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use insta::assert_snapshot;
+
+    use super::*;
+
+    #[test]
+    fn test_script_generation_pacman() {
+        let stable = generate_pacman_script(
+            "http://localhost:3000",
+            &Forge::Github,
+            "OpenBangla",
+            "OpenBangla-Keyboard",
+            &ReleaseChannel::Stable,
+        );
+        assert_snapshot!(stable);
+
+        let unstable = generate_pacman_script(
+            "http://localhost:3000",
+            &Forge::Github,
+            "OpenBangla",
+            "OpenBangla-Keyboard",
+            &ReleaseChannel::Unstable,
+        );
+        assert_snapshot!(unstable);
+    }
+}
@@ -1,3 +1,4 @@
+use crate::source::Forge;
 use crate::utils::AppVersion;
 use crate::utils::ReleaseChannel;
 use askama::Template;
@@ -6,6 +7,7 @@ use askama::Template;
 #[template(path = "rpm-script.sh", escape = "none")]
 pub(crate) struct RPMScript<'a> {
     pub(crate) host: &'a str,
+    pub(crate) forge: &'a Forge,
     pub(crate) owner: &'a str,
     pub(crate) repo: &'a str,
     pub(crate) mgr: &'a str,
@@ -14,30 +16,31 @@ pub(crate) struct RPMScript<'a> {
 }
 
 impl RPMScript<'_> {
+    /// The V2 URL ends with the `ID` of the os-release file, substituted by the script, and
+    /// the `$releasever` variable, substituted by the package manager. They override the
+    /// detection of the distribution from the user-agent. The `$basearch` variable limits
+    /// the repository to the packages of the architecture of the client.
     pub(crate) fn base_url(&self) -> String {
         match self.ver {
             AppVersion::V1 => format!(
-                "{}/{}/rpm/github/{}/{}",
-                self.host, "v1", self.owner, self.repo
+                "{}/{}/rpm/{}/{}/{}",
+                self.host, "v1", self.forge, self.owner, self.repo
             ),
             AppVersion::V2 => format!(
-                "{}/{}/rpm/github/{}/{}/{}",
-                self.host, "v2", self.owner, self.repo, self.channel
+                "{}/{}/rpm/{}/{}/{}/{}/$ID/\\$releasever/\\$basearch",
+                self.host, "v2", self.forge, self.owner, self.repo, self.channel
             ),
         }
     }
 
     pub(crate) fn repo_name(&self) -> String {
-        match self.channel {
-            ReleaseChannel::Stable => self.repo.to_string(),
-            ReleaseChannel::Unstable => format!("{}-unstable", self.repo),
-        }
+        super::repo_id(self.repo, self.channel)
     }
 
     pub(crate) fn name(&self) -> String {
         match self.channel {
             ReleaseChannel::Stable => self.repo.to_string(),
-            ReleaseChannel::Unstable => format!("{} (unstable)", self.repo),
+            channel => format!("{} ({channel})", self.repo),
         }
     }
 }
@@ -46,15 +49,17 @@ impl RPMScript<'_> {
 ///
 /// When `ver` is V1, the `channel` is ignored.
 pub(crate) fn generate_rpm_script(
+    host: &str,
+    forge: &Forge,
     owner: &str,
     repo: &str,
     mgr: &str,
     ver: &AppVersion,
     channel: &ReleaseChannel,
 ) -> String {
-    let host = dotenvy::var("PACKHUB_DOMAIN").unwrap();
     let script = RPMScript {
-        host: &host,
+        host,
+        forge,
         owner,
         repo,
         mgr,
@@ -73,6 +78,8 @@ mod tests {
     #[test]
     fn test_script_generation_rpm_v1() {
         let yum = generate_rpm_script(
+            "http://localhost:3000",
+            &Forge::Github,
             "OpenBangla",
             "OpenBangla-Keyboard",
             "yum.repos.d",
@@ -82,6 +89,8 @@ mod tests {
         assert_snapshot!(yum);
 
         let zypp = generate_rpm_script(
+            "http://localhost:3000",
+            &Forge::Github,
             "OpenBangla",
             "OpenBangla-Keyboard",
             "zypp/repos.d",
@@ -94,6 +103,8 @@ mod tests {
     #[test]
     fn test_script_generation_rpm_v2() {
         let yum = generate_rpm_script(
+            "http://localhost:3000",
+            &Forge::Github,
             "OpenBangla",
             "OpenBangla-Keyboard",
             "yum.repos.d",
@@ -103,6 +114,8 @@ mod tests {
         assert_snapshot!(yum);
 
         let zypp = generate_rpm_script(
+            "http://localhost:3000",
+            &Forge::Github,
             "OpenBangla",
             "OpenBangla-Keyboard",
             "zypp/repos.d",
@@ -112,6 +125,8 @@ mod tests {
         assert_snapshot!(zypp);
 
         let yum_unstable = generate_rpm_script(
+            "http://localhost:3000",
+            &Forge::Github,
             "OpenBangla",
             "OpenBangla-Keyboard",
             "yum.repos.d",
@@ -121,6 +136,8 @@ mod tests {
         assert_snapshot!(yum_unstable);
 
         let zypp_unstable = generate_rpm_script(
+            "http://localhost:3000",
+            &Forge::Github,
             "OpenBangla",
             "OpenBangla-Keyboard",
             "zypp/repos.d",
@@ -128,5 +145,16 @@ mod tests {
             &ReleaseChannel::Unstable,
         );
         assert_snapshot!(zypp_unstable);
+
+        let yum_pinned = generate_rpm_script(
+            "http://localhost:3000",
+            &Forge::Github,
+            "OpenBangla",
+            "OpenBangla-Keyboard",
+            "yum.repos.d",
+            &AppVersion::V2,
+            &ReleaseChannel::Range("~2.0".parse().unwrap()),
+        );
+        assert_snapshot!(yum_pinned);
     }
 }
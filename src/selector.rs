@@ -5,6 +5,17 @@ use std::collections::HashMap;
 
 use crate::{package::Package, utils::Dist};
 
+/// Select the packages of every release for the distribution.
+///
+/// Each release is selected separately, so that a package is kept in every version it was released.
+pub(crate) fn select_releases(releases: &[Vec<Package>], dist: Dist) -> Vec<Package> {
+    releases
+        .iter()
+        .flat_map(|packages| select_packages(packages, dist.clone()))
+        .cloned()
+        .collect()
+}
+
 pub(crate) fn select_packages(from: &[Package], dist: Dist) -> Vec<&Package> {
     let mut packages = Vec::new();
 
@@ -20,10 +31,10 @@ pub(crate) fn select_packages(from: &[Package], dist: Dist) -> Vec<&Package> {
 
     // Loosely match the distribution (without regarding the distribution version).
     for package in packages.iter() {
-        if let Some(pack_dist) = package.distribution() {
-            if dist.matches_distribution(pack_dist) {
-                selective.push(*package);
-            }
+        if let Some(pack_dist) = package.distribution()
+            && dist.matches_distribution(pack_dist)
+        {
+            selective.push(*package);
         }
     }
 
@@ -42,8 +53,12 @@ pub(crate) fn select_packages(from: &[Package], dist: Dist) -> Vec<&Package> {
 
         // Sort the packages by target distribution version and cut off greater versions.
         for (_, packages) in packages_by_name.iter_mut() {
-            packages.sort_by(|a, b| b.distribution().unwrap().cmp(a.distribution().unwrap()));
-            packages.retain(|i| dist >= *i.distribution().unwrap());
+            // Versions are compared, as the packages can be built for a compatible distribution.
+            packages.sort_by(|a, b| {
+                let version = |p: &Package| p.distribution().unwrap().version().cloned();
+                version(b).cmp(&version(a))
+            });
+            packages.retain(|i| dist.version() >= i.distribution().unwrap().version());
         }
 
         // Group by name and architecture
@@ -80,7 +95,7 @@ pub(crate) fn select_packages(from: &[Package], dist: Dist) -> Vec<&Package> {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::package::tests::package;
+    use crate::package::tests::{package, package_with_ver};
 
     fn openbangla_keyboard_packages() -> Vec<Package> {
         [
@@ -123,7 +138,7 @@ mod tests {
     // Because `select_packages` sorts the packages by the distribution
     // which can change the order of the packages when multiple packages
     // are present. So we need to sort the packages by their file name.
-    fn sort<'a>(mut v: Vec<&'a Package>) -> Vec<&'a Package> {
+    fn sort(mut v: Vec<&Package>) -> Vec<&Package> {
         v.sort();
         v
     }
@@ -156,6 +171,25 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_package_selection_rhel() {
+        let packages = vec![
+            package("tool-1.2.0-1.el8.x86_64.rpm"),
+            package("tool-1.2.0-1.el9.x86_64.rpm"),
+            package("tool-1.2.0-1.fc40.x86_64.rpm"),
+        ];
+
+        // Rebuilds of RHEL get the packages built for RHEL.
+        assert_eq!(
+            select_packages(&packages, Dist::Rocky(Some(semver::Version::new(9, 3, 0)))),
+            vec![&package("tool-1.2.0-1.el9.x86_64.rpm")]
+        );
+        assert_eq!(
+            select_packages(&packages, Dist::Alma(Some(semver::Version::new(8, 10, 0)))),
+            vec![&package("tool-1.2.0-1.el8.x86_64.rpm")]
+        );
+    }
+
     #[test]
     fn test_package_selection_debian() {
         let packages: Vec<Package> = openbangla_keyboard_packages();
@@ -256,4 +290,29 @@ mod tests {
             vec![&package("caprine_2.60.3_amd64.deb")]
         );
     }
+
+    #[test]
+    fn test_release_selection() {
+        let releases = [
+            vec![
+                package_with_ver("caprine_2.60.3_amd64.deb", "v2.60.3"),
+                package_with_ver("caprine-2.60.3.x86_64.rpm", "v2.60.3"),
+            ],
+            vec![
+                package_with_ver("caprine_2.60.1_amd64.deb", "v2.60.1"),
+                package_with_ver("caprine-2.60.1.x86_64.rpm", "v2.60.1"),
+            ],
+        ];
+
+        let selected = select_releases(&releases, Dist::ubuntu("24.04"));
+
+        assert_eq!(
+            selected,
+            vec![
+                package_with_ver("caprine_2.60.3_amd64.deb", "v2.60.3"),
+                package_with_ver("caprine_2.60.1_amd64.deb", "v2.60.1"),
+            ]
+        );
+        assert_eq!(selected[1].version(), "v2.60.1");
+    }
 }
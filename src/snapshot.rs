@@ -0,0 +1,283 @@
+//! Snapshots of the rendered index files of a repository.
+//!
+//! Index files are rendered once for each release selection and then served from the
+//! snapshot byte for byte. So `Release` and `Packages` fetched seconds apart always
+//! hash-match, even though signatures carry a timestamp and PGP signing is not deterministic.
+use anyhow::{Context, Result};
+use bson::{Binary, doc, spec::BinarySubtype};
+use chrono::{DateTime, Utc};
+use mongodb::Collection;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use tracing::warn;
+
+use crate::{
+    package::Package,
+    source::Forge,
+    state::AppState,
+    utils::{Dist, ReleaseChannel, hashsum},
+};
+
+/// Version of the rendering of the index files, bumped whenever it changes.
+///
+/// Snapshots rendered by an older version aren't served, they're rendered anew.
+const SNAPSHOT_VERSION: u32 = 1;
+
+/// Maximum size of the files of a saved snapshot, below the 16 MiB limit of MongoDB documents.
+const MAX_SNAPSHOT_SIZE: usize = 15 * 1024 * 1024;
+
+/// Identity of a snapshot.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct SnapshotKey {
+    version: u32,
+    /// Fingerprint of the configuration the index files are rendered with.
+    config: String,
+    project: String,
+    channel: String,
+    /// The distribution resolved from the user-agent of the client.
+    dist: String,
+    /// Fingerprint of the selected packages of the releases.
+    release: String,
+}
+
+#[derive(Serialize, Deserialize)]
+struct SnapshotFile {
+    name: String,
+    sha256: String,
+    data: Binary,
+}
+
+/// Rendered, compressed and signed index files of a repository.
+#[derive(Serialize, Deserialize)]
+pub struct Snapshot {
+    #[serde(flatten)]
+    key: SnapshotKey,
+    files: Vec<SnapshotFile>,
+    /// When the newest of the snapshotted packages was uploaded.
+    #[serde(skip)]
+    last_modified: Option<DateTime<Utc>>,
+}
+
+impl SnapshotKey {
+    pub fn new(
+        config: &str,
+        project: &str,
+        channel: &ReleaseChannel,
+        dist: &Dist,
+        packages: &[Package],
+    ) -> Self {
+        Self {
+            version: SNAPSHOT_VERSION,
+            config: config.to_owned(),
+            project: project.to_owned(),
+            channel: channel.to_string(),
+            dist: dist.to_string(),
+            release: release_id(packages),
+        }
+    }
+}
+
+impl Snapshot {
+    /// Create a snapshot of the `files`, a list of file paths and their content.
+    pub fn new(key: SnapshotKey, files: Vec<(String, Vec<u8>)>) -> Self {
+        let files = files
+            .into_iter()
+            .map(|(name, bytes)| SnapshotFile {
+                name,
+                sha256: hashsum::<Sha256>(&bytes),
+                data: Binary {
+                    subtype: BinarySubtype::Generic,
+                    bytes,
+                },
+            })
+            .collect();
+
+        Self {
+            key,
+            files,
+            last_modified: None,
+        }
+    }
+
+    /// Date the index files with the upload of the newest of the `packages`.
+    pub fn modified_by(mut self, packages: &[Package]) -> Self {
+        self.last_modified = packages.iter().map(|p| *p.creation_date()).max();
+        self
+    }
+
+    /// When the index files last changed, if it's known.
+    pub fn last_modified(&self) -> Option<DateTime<Utc>> {
+        self.last_modified
+    }
+
+    /// Content of the file at `path`.
+    pub fn file(&self, path: &str) -> Option<&[u8]> {
+        self.files
+            .iter()
+            .find(|file| file.name == path)
+            .map(|file| file.data.bytes.as_slice())
+    }
+
+    /// Find the content of the file with the SHA256 `hash` among the snapshots of the `project`.
+    ///
+    /// Files of older snapshots stay available, so clients which fetched an older
+    /// release index can still download the files it lists.
+    pub async fn find_by_hash(
+        collection: &Collection<Snapshot>,
+        project: &str,
+        hash: &str,
+    ) -> Result<Option<Vec<u8>>> {
+        let snapshot = collection
+            .find_one(doc! { "project": project, "files.sha256": hash })
+            .await?;
+
+        Ok(snapshot
+            .and_then(|snapshot| snapshot.files.into_iter().find(|file| file.sha256 == hash))
+            .map(|file| file.data.bytes))
+    }
+
+    pub async fn retrieve_from(
+        collection: &Collection<Snapshot>,
+        key: &SnapshotKey,
+    ) -> Result<Option<Self>> {
+        Ok(collection.find_one(bson::to_document(key)?).await?)
+    }
+
+    /// Save the snapshot unless one with the same key already exists.
+    ///
+    /// The saved snapshot is returned, so that concurrent requests serve the same files.
+    /// Snapshots larger than `MAX_SNAPSHOT_SIZE` aren't saved, they're returned as is.
+    pub async fn save_to(self, collection: &Collection<Snapshot>) -> Result<Self> {
+        let size: usize = self.files.iter().map(|file| file.data.bytes.len()).sum();
+
+        if size > MAX_SNAPSHOT_SIZE {
+            warn!(
+                "Snapshot {:?} of {size} bytes is too large to be saved, serving it unsaved",
+                self.key
+            );
+            return Ok(self);
+        }
+
+        let key = bson::to_document(&self.key)?;
+
+        collection
+            .update_one(
+                key.clone(),
+                doc! { "$setOnInsert": bson::to_document(&self)? },
+            )
+            .upsert(true)
+            .await?;
+
+        collection
+            .find_one(key)
+            .await?
+            .context("Snapshot was not saved")
+    }
+}
+
+/// Collection of the snapshots of the repositories hosted on the `forge`.
+pub fn snapshot_collection(state: &AppState, forge: Forge) -> Collection<Snapshot> {
+    // Project collections are named `owner/repo`, so this can't clash with a project.
+    state
+        .db()
+        .database(&forge.to_string())
+        .collection("snapshots")
+}
+
+/// Fingerprint of the packages, which changes when a package is added, removed or replaced.
+fn release_id(packages: &[Package]) -> String {
+    let mut packages: Vec<_> = packages.iter().collect();
+    packages.sort();
+
+    let mut hasher = Sha256::new();
+
+    for package in packages {
+        hasher.update(package.version());
+        hasher.update(package.download_url());
+        hasher.update(package.creation_date().to_rfc3339());
+        hasher.update([0]);
+    }
+
+    format!("{:x}", hasher.finalize())
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::DateTime;
+
+    use super::*;
+    use crate::package::tests::package_with_ver;
+
+    #[test]
+    fn test_key() {
+        let packages = [package_with_ver("foo_1.0.0_amd64.deb", "1.0.0")];
+        let key = |config| {
+            SnapshotKey::new(
+                config,
+                "owner/repo",
+                &ReleaseChannel::Stable,
+                &Dist::Debian(None),
+                &packages,
+            )
+        };
+
+        assert_eq!(key("config"), key("config"));
+        assert_ne!(key("config"), key("other"));
+        assert_eq!(key("config").version, SNAPSHOT_VERSION);
+    }
+
+    #[tokio::test]
+    async fn test_large_snapshot() {
+        // The client doesn't connect until an operation is run.
+        let client = mongodb::Client::with_uri_str("mongodb://localhost:27017")
+            .await
+            .unwrap();
+        let collection = client.database("github").collection("snapshots");
+
+        let key = SnapshotKey::new(
+            "config",
+            "owner/repo",
+            &ReleaseChannel::All,
+            &Dist::Debian(None),
+            &[],
+        );
+        let files = vec![("Packages".to_owned(), vec![0; MAX_SNAPSHOT_SIZE + 1])];
+
+        // It isn't saved, but still served
+        let snapshot = Snapshot::new(key, files)
+            .save_to(&collection)
+            .await
+            .unwrap();
+        assert_eq!(
+            snapshot.file("Packages").map(<[u8]>::len),
+            Some(MAX_SNAPSHOT_SIZE + 1)
+        );
+    }
+
+    #[test]
+    fn test_release_id() {
+        let a = package_with_ver("foo_1.0.0_amd64.deb", "1.0.0");
+        let b = package_with_ver("foo_1.0.0_arm64.deb", "1.0.0");
+
+        assert_eq!(
+            release_id(&[a.clone(), b.clone()]),
+            release_id(&[b.clone(), a.clone()])
+        );
+        assert_ne!(release_id(&[a.clone(), b]), release_id(&[a]));
+
+        // Re-uploading an asset changes the fingerprint
+        let reuploaded = Package::detect_package(
+            "foo_1.0.0_amd64.deb",
+            "1.0.0".to_owned(),
+            "foo_1.0.0_amd64.deb".to_owned(),
+            DateTime::parse_from_rfc3339("2024-07-01T00:00:00Z")
+                .unwrap()
+                .into(),
+        )
+        .unwrap();
+        assert_ne!(
+            release_id(&[package_with_ver("foo_1.0.0_amd64.deb", "1.0.0")]),
+            release_id(&[reuploaded])
+        );
+    }
+}
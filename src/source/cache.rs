@@ -0,0 +1,257 @@
+use std::{
+    collections::HashMap,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use anyhow::Result;
+use tracing::{debug, warn};
+
+use super::Release;
+use crate::error::PackhubError;
+
+/// Response of a conditional request made with the ETag of a cached response.
+pub enum Conditional<T> {
+    /// The resource has changed. It carries the new value and its ETag.
+    Modified(T, Option<String>),
+    /// The resource hasn't changed since the cached response.
+    NotModified,
+}
+
+#[derive(Clone)]
+struct Entry {
+    releases: Vec<Release>,
+    etag: Option<String>,
+    fetched: Instant,
+}
+
+/// In-memory cache of release listings.
+///
+/// Listings are served from the cache until they are older than the TTL. Then they
+/// are revalidated with a conditional request using their ETag, which doesn't count
+/// against the rate limit of the forge when nothing has changed. If the forge can't
+/// be reached, fails or rate limits us, the expired listing is served stale.
+pub struct ReleaseCache {
+    ttl: Duration,
+    entries: Mutex<HashMap<String, Entry>>,
+}
+
+impl ReleaseCache {
+    pub fn new(ttl: Duration) -> Self {
+        Self {
+            ttl,
+            entries: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Get the listing cached under `key`, or fetch it with `fetch`.
+    ///
+    /// `fetch` is called with the ETag of the cached listing, if there is one.
+    pub async fn get_or_fetch<F>(&self, key: String, fetch: F) -> Result<Vec<Release>>
+    where
+        F: AsyncFnOnce(Option<String>) -> Result<Conditional<Vec<Release>>>,
+    {
+        let cached = self.entries.lock().unwrap().get(&key).cloned();
+
+        if let Some(entry) = &cached
+            && entry.fetched.elapsed() < self.ttl
+        {
+            debug!(key, "Release listing served from cache");
+            return Ok(entry.releases.clone());
+        }
+
+        let etag = cached.as_ref().and_then(|entry| entry.etag.clone());
+
+        let entry = match (fetch(etag).await, cached) {
+            (Ok(Conditional::Modified(releases, etag)), _) => Entry {
+                releases,
+                etag,
+                fetched: Instant::now(),
+            },
+            (Ok(Conditional::NotModified), Some(entry)) => {
+                debug!(key, "Release listing revalidated");
+                Entry {
+                    fetched: Instant::now(),
+                    ..entry
+                }
+            }
+            // A forge or a proxy in between responded to an unconditional request with `304`.
+            (Ok(Conditional::NotModified), None) => {
+                return Err(PackhubError::UpstreamUnavailable(
+                    "the forge responded with 304 Not Modified to an unconditional request"
+                        .to_owned(),
+                )
+                .into());
+            }
+            (Err(e), Some(entry)) if is_unavailable(&e) => {
+                warn!(
+                    key,
+                    age = entry.fetched.elapsed().as_secs(),
+                    "Serving stale release listing: {e}"
+                );
+                return Ok(entry.releases);
+            }
+            (Err(e), _) => return Err(e),
+        };
+
+        let releases = entry.releases.clone();
+        self.entries.lock().unwrap().insert(key, entry);
+
+        Ok(releases)
+    }
+
+    /// Drop every listing whose key starts with `prefix`.
+    pub fn invalidate(&self, prefix: &str) {
+        self.entries
+            .lock()
+            .unwrap()
+            .retain(|key, _| !key.starts_with(prefix));
+    }
+}
+
+/// Whether the forge failed to respond, rather than responded that the listing doesn't exist.
+///
+/// Listings aren't served stale otherwise, so that deleted repositories and releases aren't.
+fn is_unavailable(e: &anyhow::Error) -> bool {
+    let unavailable = |kind: Option<&PackhubError>| {
+        matches!(
+            kind,
+            Some(PackhubError::UpstreamUnavailable(_) | PackhubError::RateLimited { .. })
+        )
+    };
+
+    unavailable(e.downcast_ref())
+        || e.chain()
+            .any(|e| e.is::<reqwest::Error>() || unavailable(e.downcast_ref()))
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::anyhow;
+
+    use super::*;
+
+    fn release(tag: &str) -> Vec<Release> {
+        vec![Release {
+            tag_name: tag.to_owned(),
+            draft: false,
+            prerelease: false,
+            assets: Vec::new(),
+        }]
+    }
+
+    fn tag(releases: &[Release]) -> &str {
+        &releases[0].tag_name
+    }
+
+    #[tokio::test]
+    async fn test_cache_hit() {
+        let cache = ReleaseCache::new(Duration::from_secs(60));
+
+        let fresh = cache
+            .get_or_fetch("a".to_owned(), async |_| {
+                Ok(Conditional::Modified(release("v1"), None))
+            })
+            .await
+            .unwrap();
+        assert_eq!(tag(&fresh), "v1");
+
+        let cached = cache
+            .get_or_fetch("a".to_owned(), async |_| panic!("fetched again"))
+            .await
+            .unwrap();
+        assert_eq!(tag(&cached), "v1");
+
+        cache.invalidate("a");
+
+        let refetched = cache
+            .get_or_fetch("a".to_owned(), async |_| {
+                Ok(Conditional::Modified(release("v2"), None))
+            })
+            .await
+            .unwrap();
+        assert_eq!(tag(&refetched), "v2");
+    }
+
+    #[tokio::test]
+    async fn test_cache_revalidation() {
+        let cache = ReleaseCache::new(Duration::ZERO);
+
+        cache
+            .get_or_fetch("a".to_owned(), async |etag| {
+                assert_eq!(etag, None);
+                Ok(Conditional::Modified(
+                    release("v1"),
+                    Some("\"etag\"".to_owned()),
+                ))
+            })
+            .await
+            .unwrap();
+
+        let revalidated = cache
+            .get_or_fetch("a".to_owned(), async |etag| {
+                assert_eq!(etag.as_deref(), Some("\"etag\""));
+                Ok(Conditional::NotModified)
+            })
+            .await
+            .unwrap();
+        assert_eq!(tag(&revalidated), "v1");
+
+        let stale = cache
+            .get_or_fetch("a".to_owned(), async |_| {
+                Err(PackhubError::RateLimited { retry_after: None }.into())
+            })
+            .await
+            .unwrap();
+        assert_eq!(tag(&stale), "v1");
+
+        let stale = cache
+            .get_or_fetch("a".to_owned(), async |_| {
+                Err(PackhubError::UpstreamUnavailable("502 Bad Gateway".to_owned()).into())
+            })
+            .await
+            .unwrap();
+        assert_eq!(tag(&stale), "v1");
+
+        let missing = cache
+            .get_or_fetch("b".to_owned(), async |_| {
+                Err(PackhubError::RateLimited { retry_after: None }.into())
+            })
+            .await;
+        assert!(missing.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_cache_errors() {
+        let cache = ReleaseCache::new(Duration::ZERO);
+
+        cache
+            .get_or_fetch("a".to_owned(), async |_| {
+                Ok(Conditional::Modified(release("v1"), None))
+            })
+            .await
+            .unwrap();
+
+        // Deleted repositories aren't served stale
+        let deleted = cache
+            .get_or_fetch("a".to_owned(), async |_| {
+                Err(PackhubError::UnknownRepository("owner/repo".to_owned()).into())
+            })
+            .await;
+        assert!(deleted.is_err());
+
+        let invalid = cache
+            .get_or_fetch("a".to_owned(), async |_| Err(anyhow!("invalid listing")))
+            .await;
+        assert!(invalid.is_err());
+
+        // Not modified without a cached listing
+        let unexpected = cache
+            .get_or_fetch("b".to_owned(), async |etag| {
+                assert_eq!(etag, None);
+                Ok(Conditional::NotModified)
+            })
+            .await;
+        assert!(unexpected.is_err());
+    }
+}
@@ -0,0 +1,220 @@
+use std::time::Duration;
+
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use reqwest::Url;
+use serde::Deserialize;
+
+use super::{
+    Asset, Release, ReleaseSource,
+    cache::{Conditional, ReleaseCache},
+    conditional_get, paginate, single,
+};
+use crate::{REQWEST, error::PackhubError};
+
+/// Releases hosted on Gitea or Forgejo (e.g. Codeberg or a self-hosted instance).
+pub struct Gitea {
+    base: Url,
+    token: Option<String>,
+    cache: ReleaseCache,
+}
+
+#[derive(Deserialize)]
+struct GiteaRelease {
+    tag_name: String,
+    draft: bool,
+    prerelease: bool,
+    assets: Vec<GiteaAsset>,
+}
+
+#[derive(Deserialize)]
+struct GiteaAsset {
+    name: String,
+    created_at: DateTime<Utc>,
+    browser_download_url: String,
+}
+
+impl From<GiteaRelease> for Release {
+    fn from(release: GiteaRelease) -> Self {
+        let assets = release
+            .assets
+            .into_iter()
+            .map(|asset| Asset {
+                name: asset.name,
+                url: asset.browser_download_url,
+                // Attachments can't be modified in place, so the creation date is the last update.
+                updated_at: asset.created_at,
+            })
+            .collect();
+
+        Release {
+            tag_name: release.tag_name,
+            draft: release.draft,
+            prerelease: release.prerelease,
+            assets,
+        }
+    }
+}
+
+impl Gitea {
+    /// Create a Gitea release source for the instance at `base` (e.g. `https://codeberg.org`).
+    ///
+    /// Release listings are cached for `ttl`.
+    pub fn new(base: &str, token: Option<String>, ttl: Duration) -> Result<Self> {
+        Ok(Self {
+            base: Url::parse(base)?,
+            token,
+            cache: ReleaseCache::new(ttl),
+        })
+    }
+
+    /// Build the API URL of the repository releases endpoint followed by `path`.
+    fn releases_url(&self, owner: &str, repo: &str, path: &[&str]) -> Url {
+        let mut url = self.base.clone();
+        url.path_segments_mut()
+            .expect("Gitea base URL can't be a base")
+            .pop_if_empty()
+            .extend(["api", "v1", "repos", owner, repo, "releases"])
+            .extend(path);
+        url
+    }
+
+    async fn get(
+        &self,
+        url: Url,
+        etag: Option<String>,
+        not_found: PackhubError,
+    ) -> Result<Conditional<String>> {
+        let mut request = REQWEST.get(url);
+
+        if let Some(token) = &self.token {
+            request = request.header("Authorization", format!("token {token}"));
+        }
+
+        conditional_get(request, etag, not_found).await
+    }
+
+    /// Fetch the release at `url` through the cache.
+    async fn release(&self, key: String, url: Url, not_found: PackhubError) -> Result<Release> {
+        let releases = self
+            .cache
+            .get_or_fetch(key, async |etag| {
+                self.get(url, etag, not_found)
+                    .await?
+                    .try_map(|body| Ok(vec![serde_json::from_str::<GiteaRelease>(&body)?.into()]))
+            })
+            .await?;
+
+        single(releases)
+    }
+
+    /// Drop the cached release listings of the repository.
+    pub fn invalidate(&self, owner: &str, repo: &str) {
+        self.cache.invalidate(&format!("{owner}/{repo}/"));
+    }
+}
+
+impl ReleaseSource for Gitea {
+    async fn latest_release(&self, owner: &str, repo: &str) -> Result<Release> {
+        let url = self.releases_url(owner, repo, &["latest"]);
+
+        let not_found = PackhubError::NoRelease(format!("No release of {owner}/{repo} found"));
+
+        self.release(format!("{owner}/{repo}/latest"), url, not_found)
+            .await
+    }
+
+    async fn release_by_tag(&self, owner: &str, repo: &str, tag: &str) -> Result<Release> {
+        let url = self.releases_url(owner, repo, &["tags", tag]);
+
+        let not_found =
+            PackhubError::NoRelease(format!("No release {tag} of {owner}/{repo} found"));
+
+        self.release(format!("{owner}/{repo}/tags/{tag}"), url, not_found)
+            .await
+    }
+
+    async fn releases(&self, owner: &str, repo: &str, count: usize) -> Result<Vec<Release>> {
+        // Gitea returns releases in reverse-chronological order (newest first), at most 50
+        // per page by default.
+        let limit = count.clamp(1, 50);
+        let url = |page: usize| {
+            let mut url = self.releases_url(owner, repo, &[]);
+            url.query_pairs_mut()
+                .append_pair("limit", &limit.to_string())
+                .append_pair("page", &page.to_string());
+            url
+        };
+        let not_found = || PackhubError::UnknownRepository(format!("{owner}/{repo}"));
+
+        self.cache
+            .get_or_fetch(format!("{owner}/{repo}/releases/{count}"), async |etag| {
+                paginate::<GiteaRelease, _>(count, limit, etag, |page, etag| {
+                    self.get(url(page), etag, not_found())
+                })
+                .await
+            })
+            .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        super::tests::{assert_pagination, serve_releases},
+        *,
+    };
+
+    #[test]
+    fn test_releases_url() {
+        let gitea = Gitea::new("https://codeberg.org", None, Duration::ZERO).unwrap();
+
+        assert_eq!(
+            gitea
+                .releases_url("owner", "repo", &["tags", "v1.0.0"])
+                .as_str(),
+            "https://codeberg.org/api/v1/repos/owner/repo/releases/tags/v1.0.0"
+        );
+    }
+
+    #[test]
+    fn test_release_conversion() {
+        let json = r#"{
+            "id": 42,
+            "tag_name": "v0.9.0",
+            "draft": false,
+            "prerelease": true,
+            "created_at": "2024-07-01T00:00:00Z",
+            "published_at": "2024-07-01T00:00:00Z",
+            "assets": [{
+                "id": 7,
+                "name": "tool-0.9.0-1.x86_64.rpm",
+                "size": 1024,
+                "download_count": 3,
+                "created_at": "2024-07-01T00:05:00Z",
+                "uuid": "1b3f",
+                "browser_download_url": "https://codeberg.org/owner/repo/releases/download/v0.9.0/tool-0.9.0-1.x86_64.rpm"
+            }]
+        }"#;
+
+        let release: Release = serde_json::from_str::<GiteaRelease>(json).unwrap().into();
+
+        assert_eq!(release.tag_name, "v0.9.0");
+        assert!(!release.draft);
+        assert!(release.prerelease);
+        assert_eq!(
+            release.assets[0].url,
+            "https://codeberg.org/owner/repo/releases/download/v0.9.0/tool-0.9.0-1.x86_64.rpm"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_releases_pagination() {
+        let base = serve_releases("/api/v1/repos/{owner}/{repo}/releases", "limit", 250, |i| {
+            format!(r#"{{"tag_name":"v{i}","draft":false,"prerelease":false,"assets":[]}}"#)
+        })
+        .await;
+
+        assert_pagination(&Gitea::new(&base, None, Duration::ZERO).unwrap()).await;
+    }
+}
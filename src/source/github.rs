@@ -0,0 +1,166 @@
+use std::time::Duration;
+
+use anyhow::Result;
+use axum::http::{
+    HeaderMap, StatusCode,
+    header::{ETAG, IF_NONE_MATCH},
+};
+use octocrab::{Octocrab, models::repos::Release as GitHubRelease};
+use reqwest::Url;
+
+use super::{
+    Asset, Release, ReleaseSource,
+    cache::{Conditional, ReleaseCache},
+    paginate, response_error, single,
+};
+use crate::error::PackhubError;
+
+/// Releases hosted on GitHub.
+pub struct GitHub {
+    octo: Octocrab,
+    cache: ReleaseCache,
+}
+
+impl From<GitHubRelease> for Release {
+    fn from(release: GitHubRelease) -> Self {
+        let assets = release
+            .assets
+            .into_iter()
+            .map(|asset| Asset {
+                name: asset.name,
+                url: asset.browser_download_url.to_string(),
+                updated_at: asset.updated_at,
+            })
+            .collect();
+
+        Release {
+            tag_name: release.tag_name,
+            draft: release.draft,
+            prerelease: release.prerelease,
+            assets,
+        }
+    }
+}
+
+impl GitHub {
+    /// Create a GitHub release source. Release listings are cached for `ttl`.
+    pub fn new(octo: Octocrab, ttl: Duration) -> Self {
+        Self {
+            octo,
+            cache: ReleaseCache::new(ttl),
+        }
+    }
+
+    /// Send a GET request to the API `route`, conditional on the resource not matching the `etag`.
+    ///
+    /// `304 Not Modified` responses don't count against the rate limit.
+    ///
+    /// `not_found` is the error when the resource doesn't exist.
+    async fn get(
+        &self,
+        route: String,
+        etag: Option<String>,
+        not_found: PackhubError,
+    ) -> Result<Conditional<String>> {
+        let mut headers = HeaderMap::new();
+
+        if let Some(etag) = etag {
+            headers.insert(IF_NONE_MATCH, etag.parse()?);
+        }
+
+        let response = self
+            .octo
+            ._get_with_headers(route, Some(headers))
+            .await
+            .map_err(|e| PackhubError::UpstreamUnavailable(e.to_string()))?;
+
+        if response.status() == StatusCode::NOT_MODIFIED {
+            return Ok(Conditional::NotModified);
+        }
+
+        if !response.status().is_success() {
+            return Err(response_error(response.status(), response.headers(), not_found).into());
+        }
+
+        let etag = response
+            .headers()
+            .get(ETAG)
+            .and_then(|etag| etag.to_str().ok())
+            .map(ToOwned::to_owned);
+
+        Ok(Conditional::Modified(
+            self.octo.body_to_string(response).await?,
+            etag,
+        ))
+    }
+
+    /// Fetch the release at the API `route` through the cache.
+    async fn release(
+        &self,
+        key: String,
+        route: String,
+        not_found: PackhubError,
+    ) -> Result<Release> {
+        let releases = self
+            .cache
+            .get_or_fetch(key, async |etag| {
+                self.get(route, etag, not_found)
+                    .await?
+                    .try_map(|body| Ok(vec![serde_json::from_str::<GitHubRelease>(&body)?.into()]))
+            })
+            .await?;
+
+        single(releases)
+    }
+
+    /// Drop the cached release listings of the repository.
+    pub fn invalidate(&self, owner: &str, repo: &str) {
+        self.cache.invalidate(&format!("{owner}/{repo}/"));
+    }
+}
+
+impl ReleaseSource for GitHub {
+    async fn latest_release(&self, owner: &str, repo: &str) -> Result<Release> {
+        let route = format!("/repos/{owner}/{repo}/releases/latest");
+
+        let not_found = PackhubError::NoRelease(format!("No release of {owner}/{repo} found"));
+
+        self.release(format!("{owner}/{repo}/latest"), route, not_found)
+            .await
+    }
+
+    async fn release_by_tag(&self, owner: &str, repo: &str, tag: &str) -> Result<Release> {
+        // Tags can contain characters (like `/`) which have to be percent-encoded.
+        let mut url = Url::parse("https://api.github.com").unwrap();
+        url.path_segments_mut()
+            .unwrap()
+            .extend(["repos", owner, repo, "releases", "tags", tag]);
+
+        let not_found =
+            PackhubError::NoRelease(format!("No release {tag} of {owner}/{repo} found"));
+
+        self.release(
+            format!("{owner}/{repo}/tags/{tag}"),
+            url.path().to_owned(),
+            not_found,
+        )
+        .await
+    }
+
+    async fn releases(&self, owner: &str, repo: &str, count: usize) -> Result<Vec<Release>> {
+        // GitHub returns releases in reverse-chronological order (newest first).
+        let per_page = count.clamp(1, 100);
+        let route =
+            |page: usize| format!("/repos/{owner}/{repo}/releases?per_page={per_page}&page={page}");
+        let not_found = || PackhubError::UnknownRepository(format!("{owner}/{repo}"));
+
+        self.cache
+            .get_or_fetch(format!("{owner}/{repo}/releases/{count}"), async |etag| {
+                paginate::<GitHubRelease, _>(count, per_page, etag, |page, etag| {
+                    self.get(route(page), etag, not_found())
+                })
+                .await
+            })
+            .await
+    }
+}
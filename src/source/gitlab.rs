@@ -0,0 +1,268 @@
+use std::time::Duration;
+
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use reqwest::Url;
+use serde::Deserialize;
+
+use super::{
+    Asset, Release, ReleaseSource,
+    cache::{Conditional, ReleaseCache},
+    conditional_get, paginate, single,
+};
+use crate::{REQWEST, error::PackhubError};
+
+/// Releases hosted on GitLab (gitlab.com or a self-hosted instance).
+pub struct GitLab {
+    base: Url,
+    token: Option<String>,
+    cache: ReleaseCache,
+}
+
+#[derive(Deserialize)]
+struct GitLabRelease {
+    tag_name: String,
+    /// Releases with a future release date are treated as pre-releases.
+    #[serde(default)]
+    upcoming_release: bool,
+    released_at: Option<DateTime<Utc>>,
+    created_at: DateTime<Utc>,
+    assets: GitLabAssets,
+}
+
+#[derive(Deserialize)]
+struct GitLabAssets {
+    links: Vec<GitLabLink>,
+}
+
+#[derive(Deserialize)]
+struct GitLabLink {
+    name: String,
+    url: String,
+    direct_asset_url: Option<String>,
+}
+
+impl From<GitLabRelease> for Release {
+    fn from(release: GitLabRelease) -> Self {
+        // Release links don't carry their own timestamps.
+        let updated_at = release.released_at.unwrap_or(release.created_at);
+
+        let assets = release
+            .assets
+            .links
+            .into_iter()
+            .map(|link| Asset {
+                name: link.name,
+                url: link.direct_asset_url.unwrap_or(link.url),
+                updated_at,
+            })
+            .collect();
+
+        Release {
+            tag_name: release.tag_name,
+            draft: false,
+            prerelease: release.upcoming_release,
+            assets,
+        }
+    }
+}
+
+impl GitLab {
+    /// Create a GitLab release source for the instance at `base` (e.g. `https://gitlab.com`).
+    ///
+    /// Release listings are cached for `ttl`.
+    pub fn new(base: &str, token: Option<String>, ttl: Duration) -> Result<Self> {
+        Ok(Self {
+            base: Url::parse(base)?,
+            token,
+            cache: ReleaseCache::new(ttl),
+        })
+    }
+
+    /// Build the API URL of the project releases endpoint followed by `path`.
+    fn releases_url(&self, owner: &str, repo: &str, path: &[&str]) -> Url {
+        let mut url = self.base.clone();
+        url.path_segments_mut()
+            .expect("GitLab base URL can't be a base")
+            .pop_if_empty()
+            .extend(["api", "v4", "projects"])
+            // The project is identified by its URL-encoded path.
+            .push(&format!("{owner}/{repo}"))
+            .push("releases")
+            .extend(path);
+        url
+    }
+
+    async fn get(
+        &self,
+        url: Url,
+        etag: Option<String>,
+        not_found: PackhubError,
+    ) -> Result<Conditional<String>> {
+        let mut request = REQWEST.get(url);
+
+        if let Some(token) = &self.token {
+            request = request.header("PRIVATE-TOKEN", token);
+        }
+
+        conditional_get(request, etag, not_found).await
+    }
+
+    /// Fetch the release at `url` through the cache.
+    async fn release(&self, key: String, url: Url, not_found: PackhubError) -> Result<Release> {
+        let releases = self
+            .cache
+            .get_or_fetch(key, async |etag| {
+                self.get(url, etag, not_found)
+                    .await?
+                    .try_map(|body| Ok(vec![serde_json::from_str::<GitLabRelease>(&body)?.into()]))
+            })
+            .await?;
+
+        single(releases)
+    }
+
+    /// Drop the cached release listings of the project.
+    pub fn invalidate(&self, owner: &str, repo: &str) {
+        self.cache.invalidate(&format!("{owner}/{repo}/"));
+    }
+}
+
+impl ReleaseSource for GitLab {
+    async fn latest_release(&self, owner: &str, repo: &str) -> Result<Release> {
+        let url = self.releases_url(owner, repo, &["permalink", "latest"]);
+
+        let not_found = PackhubError::NoRelease(format!("No release of {owner}/{repo} found"));
+
+        self.release(format!("{owner}/{repo}/latest"), url, not_found)
+            .await
+    }
+
+    async fn release_by_tag(&self, owner: &str, repo: &str, tag: &str) -> Result<Release> {
+        let url = self.releases_url(owner, repo, &[tag]);
+
+        let not_found =
+            PackhubError::NoRelease(format!("No release {tag} of {owner}/{repo} found"));
+
+        self.release(format!("{owner}/{repo}/tags/{tag}"), url, not_found)
+            .await
+    }
+
+    async fn releases(&self, owner: &str, repo: &str, count: usize) -> Result<Vec<Release>> {
+        // GitLab returns releases sorted by their release date (newest first).
+        let per_page = count.clamp(1, 100);
+        let url = |page: usize| {
+            let mut url = self.releases_url(owner, repo, &[]);
+            url.query_pairs_mut()
+                .append_pair("per_page", &per_page.to_string())
+                .append_pair("page", &page.to_string());
+            url
+        };
+        let not_found = || PackhubError::UnknownRepository(format!("{owner}/{repo}"));
+
+        self.cache
+            .get_or_fetch(format!("{owner}/{repo}/releases/{count}"), async |etag| {
+                paginate::<GitLabRelease, _>(count, per_page, etag, |page, etag| {
+                    self.get(url(page), etag, not_found())
+                })
+                .await
+            })
+            .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        super::tests::{assert_pagination, serve_releases},
+        *,
+    };
+    use crate::package::Package;
+
+    #[test]
+    fn test_releases_url() {
+        let gitlab = GitLab::new("https://gitlab.example.com/", None, Duration::ZERO).unwrap();
+
+        assert_eq!(
+            gitlab
+                .releases_url("group", "project", &["v1.0/rc"])
+                .as_str(),
+            "https://gitlab.example.com/api/v4/projects/group%2Fproject/releases/v1.0%2Frc"
+        );
+    }
+
+    #[test]
+    fn test_release_conversion() {
+        let json = r#"{
+            "tag_name": "v1.2.0",
+            "upcoming_release": false,
+            "released_at": "2024-07-01T00:00:00Z",
+            "created_at": "2024-06-30T00:00:00Z",
+            "assets": {
+                "count": 1,
+                "links": [{
+                    "id": 1,
+                    "name": "tool_1.2.0_amd64.deb",
+                    "url": "https://gitlab.example.com/group/project/-/package_files/1/download",
+                    "direct_asset_url": "https://gitlab.example.com/group/project/-/releases/v1.2.0/downloads/tool_1.2.0_amd64.deb",
+                    "link_type": "package"
+                }],
+                "sources": []
+            }
+        }"#;
+
+        let release: Release = serde_json::from_str::<GitLabRelease>(json).unwrap().into();
+
+        assert_eq!(release.tag_name, "v1.2.0");
+        assert!(!release.prerelease);
+        assert_eq!(release.assets.len(), 1);
+        assert_eq!(release.assets[0].name, "tool_1.2.0_amd64.deb");
+        assert_eq!(
+            release.assets[0].url,
+            "https://gitlab.example.com/group/project/-/releases/v1.2.0/downloads/tool_1.2.0_amd64.deb"
+        );
+    }
+
+    #[test]
+    fn test_package_link() {
+        // Generic package links only have the URL of the package file.
+        let json = r#"{
+            "tag_name": "v1.2.0",
+            "released_at": "2024-07-01T00:00:00Z",
+            "created_at": "2024-06-30T00:00:00Z",
+            "assets": {
+                "links": [{
+                    "name": "tool_1.2.0_amd64.deb",
+                    "url": "https://gitlab.example.com/group/project/-/package_files/1/download"
+                }]
+            }
+        }"#;
+
+        let release: Release = serde_json::from_str::<GitLabRelease>(json).unwrap().into();
+        let asset = &release.assets[0];
+        assert_eq!(
+            asset.url,
+            "https://gitlab.example.com/group/project/-/package_files/1/download"
+        );
+
+        let package = Package::detect_package(
+            &asset.name,
+            release.tag_name.clone(),
+            asset.url.clone(),
+            asset.updated_at,
+        )
+        .unwrap();
+        assert_eq!(package.file_name(), "tool_1.2.0_amd64.deb");
+        assert_eq!(package.download_url(), asset.url);
+    }
+
+    #[tokio::test]
+    async fn test_releases_pagination() {
+        let base = serve_releases("/api/v4/projects/{project}/releases", "per_page", 250, |i| {
+            format!(r#"{{"tag_name":"v{i}","created_at":"2024-07-01T00:00:00Z","assets":{{"links":[]}}}}"#)
+        })
+        .await;
+
+        assert_pagination(&GitLab::new(&base, None, Duration::ZERO).unwrap()).await;
+    }
+}
@@ -0,0 +1,385 @@
+//! Release sources (forges) from which packhub reads the releases of a project
+//! and the package assets attached to them.
+use std::fmt::Display;
+
+use anyhow::{Context, Result};
+use axum::http::{
+    HeaderMap, StatusCode,
+    header::{ETAG, IF_NONE_MATCH, RETRY_AFTER},
+};
+use chrono::{DateTime, Utc};
+use reqwest::RequestBuilder;
+use serde::{Deserialize, de::DeserializeOwned};
+
+use crate::error::PackhubError;
+
+mod cache;
+mod gitea;
+mod github;
+mod gitlab;
+
+use self::cache::Conditional;
+pub use self::{gitea::Gitea, github::GitHub, gitlab::GitLab};
+
+/// The forge hosting a project.
+///
+/// It is the `{forge}` segment of the repository routes (`/github/`, `/gitlab/`, `/gitea/`).
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Forge {
+    Github,
+    Gitlab,
+    Gitea,
+}
+
+impl Display for Forge {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Forge::Github => write!(f, "github"),
+            Forge::Gitlab => write!(f, "gitlab"),
+            Forge::Gitea => write!(f, "gitea"),
+        }
+    }
+}
+
+/// A release of a project as reported by a release source.
+#[derive(Debug, Clone)]
+pub struct Release {
+    pub tag_name: String,
+    pub draft: bool,
+    pub prerelease: bool,
+    pub assets: Vec<Asset>,
+}
+
+/// A file attached to a release.
+#[derive(Debug, Clone)]
+pub struct Asset {
+    pub name: String,
+    /// URL from which the asset can be downloaded.
+    pub url: String,
+    pub updated_at: DateTime<Utc>,
+}
+
+/// A backend which lists the releases of a project and their assets.
+pub trait ReleaseSource {
+    /// Fetch the latest stable release.
+    fn latest_release(
+        &self,
+        owner: &str,
+        repo: &str,
+    ) -> impl Future<Output = Result<Release>> + Send;
+
+    /// Fetch the release tagged with `tag`.
+    fn release_by_tag(
+        &self,
+        owner: &str,
+        repo: &str,
+        tag: &str,
+    ) -> impl Future<Output = Result<Release>> + Send;
+
+    /// Fetch at most `count` of the most recent releases, newest first.
+    ///
+    /// Draft and pre-releases are included.
+    fn releases(
+        &self,
+        owner: &str,
+        repo: &str,
+        count: usize,
+    ) -> impl Future<Output = Result<Vec<Release>>> + Send;
+}
+
+/// The release source selected for a request.
+#[derive(Clone, Copy)]
+pub enum Source<'a> {
+    GitHub(&'a GitHub),
+    GitLab(&'a GitLab),
+    Gitea(&'a Gitea),
+}
+
+impl Source<'_> {
+    /// Find the download URL of the asset named `file` in the release tagged with `tag`.
+    pub async fn asset_url(
+        &self,
+        owner: &str,
+        repo: &str,
+        tag: &str,
+        file: &str,
+    ) -> Result<String> {
+        let release = self.release_by_tag(owner, repo, tag).await?;
+
+        release
+            .assets
+            .into_iter()
+            .find(|asset| asset.name == file)
+            .map(|asset| asset.url)
+            .ok_or_else(|| {
+                PackhubError::Gone(format!(
+                    "Asset {file} not found in release {tag} of {owner}/{repo}"
+                ))
+                .into()
+            })
+    }
+
+    /// Drop the cached release listings of the repository.
+    pub fn invalidate(&self, owner: &str, repo: &str) {
+        match self {
+            Source::GitHub(source) => source.invalidate(owner, repo),
+            Source::GitLab(source) => source.invalidate(owner, repo),
+            Source::Gitea(source) => source.invalidate(owner, repo),
+        }
+    }
+}
+
+impl ReleaseSource for Source<'_> {
+    async fn latest_release(&self, owner: &str, repo: &str) -> Result<Release> {
+        match self {
+            Source::GitHub(source) => source.latest_release(owner, repo).await,
+            Source::GitLab(source) => source.latest_release(owner, repo).await,
+            Source::Gitea(source) => source.latest_release(owner, repo).await,
+        }
+    }
+
+    async fn release_by_tag(&self, owner: &str, repo: &str, tag: &str) -> Result<Release> {
+        match self {
+            Source::GitHub(source) => source.release_by_tag(owner, repo, tag).await,
+            Source::GitLab(source) => source.release_by_tag(owner, repo, tag).await,
+            Source::Gitea(source) => source.release_by_tag(owner, repo, tag).await,
+        }
+    }
+
+    async fn releases(&self, owner: &str, repo: &str, count: usize) -> Result<Vec<Release>> {
+        match self {
+            Source::GitHub(source) => source.releases(owner, repo, count).await,
+            Source::GitLab(source) => source.releases(owner, repo, count).await,
+            Source::Gitea(source) => source.releases(owner, repo, count).await,
+        }
+    }
+}
+
+impl<T> Conditional<T> {
+    /// Map the value of a modified response.
+    fn try_map<U>(self, f: impl FnOnce(T) -> Result<U>) -> Result<Conditional<U>> {
+        match self {
+            Conditional::Modified(value, etag) => Ok(Conditional::Modified(f(value)?, etag)),
+            Conditional::NotModified => Ok(Conditional::NotModified),
+        }
+    }
+}
+
+/// Send the `request`, conditional on the resource not matching the `etag`.
+///
+/// `not_found` is the error when the resource doesn't exist.
+async fn conditional_get(
+    mut request: RequestBuilder,
+    etag: Option<String>,
+    not_found: PackhubError,
+) -> Result<Conditional<String>> {
+    if let Some(etag) = etag {
+        request = request.header(IF_NONE_MATCH, etag);
+    }
+
+    let response = request
+        .send()
+        .await
+        .map_err(|e| PackhubError::UpstreamUnavailable(e.to_string()))?;
+
+    if response.status() == StatusCode::NOT_MODIFIED {
+        return Ok(Conditional::NotModified);
+    }
+
+    if !response.status().is_success() {
+        return Err(response_error(response.status(), response.headers(), not_found).into());
+    }
+
+    let etag = response
+        .headers()
+        .get(ETAG)
+        .and_then(|etag| etag.to_str().ok())
+        .map(ToOwned::to_owned);
+
+    Ok(Conditional::Modified(response.text().await?, etag))
+}
+
+/// The error of an unsuccessful response of a forge API.
+///
+/// `not_found` is the error when the resource doesn't exist.
+fn response_error(
+    status: StatusCode,
+    headers: &HeaderMap,
+    not_found: PackhubError,
+) -> PackhubError {
+    let header = |name| {
+        headers
+            .get(name)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.parse::<u64>().ok())
+    };
+
+    // GitHub responds with `403 Forbidden` when the rate limit is exceeded.
+    if status == StatusCode::TOO_MANY_REQUESTS
+        || (status == StatusCode::FORBIDDEN && header("x-ratelimit-remaining") == Some(0))
+    {
+        let retry_after = header(RETRY_AFTER.as_str()).or_else(|| {
+            // The time at which the rate limit resets, in UTC epoch seconds.
+            let reset = header("x-ratelimit-reset")?;
+            Some(reset.saturating_sub(Utc::now().timestamp().try_into().ok()?))
+        });
+
+        return PackhubError::RateLimited { retry_after };
+    }
+
+    match status {
+        StatusCode::NOT_FOUND => not_found,
+        status if status.is_server_error() => {
+            PackhubError::UpstreamUnavailable(format!("the forge responded with {status}"))
+        }
+        status => PackhubError::UpstreamRejected(format!("the forge responded with {status}")),
+    }
+}
+
+/// Fetch at most `count` releases of a listing, `per_page` at a time.
+///
+/// `fetch_page` fetches a page (numbered from 1) of releases of the type `T`, conditional
+/// on the ETag. Only the first page is conditional on the `etag` of the cached listing:
+/// if it hasn't changed, no release has been published or edited since.
+async fn paginate<T, F>(
+    count: usize,
+    per_page: usize,
+    etag: Option<String>,
+    fetch_page: impl Fn(usize, Option<String>) -> F,
+) -> Result<Conditional<Vec<Release>>>
+where
+    T: DeserializeOwned + Into<Release>,
+    F: Future<Output = Result<Conditional<String>>>,
+{
+    let Conditional::Modified(body, etag) = fetch_page(1, etag).await? else {
+        return Ok(Conditional::NotModified);
+    };
+
+    let mut releases: Vec<T> = serde_json::from_str(&body)?;
+    let mut page = 1;
+
+    // A page which isn't full is the last one.
+    while releases.len() < count && releases.len() == page * per_page {
+        page += 1;
+        if let Conditional::Modified(body, _) = fetch_page(page, None).await? {
+            releases.extend(serde_json::from_str::<Vec<T>>(&body)?);
+        }
+    }
+
+    releases.truncate(count);
+
+    Ok(Conditional::Modified(
+        releases.into_iter().map(Into::into).collect(),
+        etag,
+    ))
+}
+
+/// Take the release out of a listing of a single release.
+fn single(releases: Vec<Release>) -> Result<Release> {
+    releases.into_iter().next().context("Empty release listing")
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use axum::{Router, extract::Query, routing::get};
+    use tokio::net::TcpListener;
+
+    use super::*;
+
+    /// Serve `total` releases at the `route` of a forge API, paginated by the `page` and
+    /// `per_page` query parameters.
+    ///
+    /// `release` renders the release with the index in the JSON of the forge.
+    pub(super) async fn serve_releases(
+        route: &str,
+        per_page: &'static str,
+        total: usize,
+        release: fn(usize) -> String,
+    ) -> String {
+        let app = Router::new().route(
+            route,
+            get(async move |Query(query): Query<HashMap<String, usize>>| {
+                let (page, per_page) = (query["page"], query[per_page]);
+                let releases: Vec<_> = (0..total)
+                    .skip((page - 1) * per_page)
+                    .take(per_page)
+                    .map(release)
+                    .collect();
+
+                format!("[{}]", releases.join(","))
+            }),
+        );
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, app).await });
+
+        format!("http://{addr}")
+    }
+
+    /// Check that the `source` of the 250 releases served by `serve_releases` pages
+    /// through them.
+    pub(super) async fn assert_pagination(source: &impl ReleaseSource) {
+        let releases = source.releases("owner", "repo", 150).await.unwrap();
+        assert_eq!(releases.len(), 150);
+        assert_eq!(releases[0].tag_name, "v0");
+        assert_eq!(releases[149].tag_name, "v149");
+
+        let releases = source.releases("owner", "repo", 300).await.unwrap();
+        assert_eq!(releases.len(), 250);
+    }
+
+    #[test]
+    fn test_response_error() {
+        let error = |status, headers: &[(&'static str, &str)]| {
+            let mut map = HeaderMap::new();
+            for (name, value) in headers {
+                map.insert(*name, value.parse().unwrap());
+            }
+            response_error(
+                status,
+                &map,
+                PackhubError::UnknownRepository("owner/repo".to_owned()),
+            )
+        };
+
+        assert!(matches!(
+            error(StatusCode::NOT_FOUND, &[]),
+            PackhubError::UnknownRepository(_)
+        ));
+        assert!(matches!(
+            error(StatusCode::TOO_MANY_REQUESTS, &[("retry-after", "30")]),
+            PackhubError::RateLimited {
+                retry_after: Some(30)
+            }
+        ));
+
+        // GitHub's primary rate limit
+        let reset = (Utc::now().timestamp() + 120).to_string();
+        let PackhubError::RateLimited {
+            retry_after: Some(retry_after),
+        } = error(
+            StatusCode::FORBIDDEN,
+            &[
+                ("x-ratelimit-remaining", "0"),
+                ("x-ratelimit-reset", &reset),
+            ],
+        )
+        else {
+            panic!("Not rate limited");
+        };
+        assert!((110..=120).contains(&retry_after));
+
+        assert!(matches!(
+            error(StatusCode::FORBIDDEN, &[("x-ratelimit-remaining", "10")]),
+            PackhubError::UpstreamRejected(_)
+        ));
+        assert!(matches!(
+            error(StatusCode::BAD_GATEWAY, &[]),
+            PackhubError::UpstreamUnavailable(_)
+        ));
+    }
+}
@@ -1,13 +1,31 @@
-use std::sync::Arc;
+use std::{
+    collections::HashMap,
+    sync::{Arc, RwLock},
+};
 
-use anyhow::Result;
-use dotenvy::var;
+use anyhow::{Context, Result};
 use mongodb::Client;
-use octocrab::{Octocrab, OctocrabBuilder};
+use octocrab::OctocrabBuilder;
+use rsa::{
+    RsaPrivateKey,
+    pkcs8::{EncodePublicKey, LineEnding},
+};
 use sequoia_openpgp::{Cert, crypto::Password, serialize::SerializeInto};
+use sha2::Sha256;
+use tracing::warn;
 
-use crate::pgp::{
-    clearsign_metadata, detached_sign_metadata, generate_and_save_keys, load_cert_from_file,
+use crate::{
+    apk::{generate_and_save_rsa_key, load_rsa_key_from_file, rsa_sign},
+    blob::BlobStore,
+    config::Config,
+    pgp::{
+        clearsign_metadata, detached_sign_data, detached_sign_metadata, generate_and_save_keys,
+        load_cert_from_file,
+    },
+    platform::{AptPlatformDetection, AptReleases, apt_releases_collection, refresh_apt_releases},
+    proxy::DownloadMode,
+    source::{Forge, GitHub, GitLab, Gitea, Source},
+    utils::{Arch, hashsum},
 };
 
 #[derive(Clone)]
@@ -18,47 +36,134 @@ pub struct AppState {
 struct InnerState {
     db: Client,
     cert: Cert,
-    github: Octocrab,
+    rsa_key: RsaPrivateKey,
+    github: GitHub,
+    gitlab: GitLab,
+    gitea: Gitea,
     passphrase: Password,
+    /// Public URL of the deployment, without a trailing `/`.
+    base_url: String,
+    all_releases: usize,
+    webhook_secret: Option<String>,
+    apt_platform: RwLock<Arc<AptPlatformDetection>>,
+    apt_architectures: Vec<Arch>,
+    /// Fingerprint of the configuration the index files are rendered with.
+    render_config: String,
+    max_asset_size: u64,
+    blob_store: Option<Arc<BlobStore>>,
+    download_modes: DownloadModes,
 }
 
 impl AppState {
-    pub async fn initialize(generate_keys: bool) -> Self {
-        let uri = format!(
-            "mongodb://{}:{}@{}:27017",
-            var("PACKHUB_DB_USER").unwrap(),
-            var("PACKHUB_DB_PASSWORD").unwrap(),
-            var("PACKHUB_DB_HOST").unwrap()
-        );
+    pub async fn initialize(config: &Config, generate_keys: bool) -> Result<Self> {
+        let db = Client::with_uri_str(config.database.uri()?)
+            .await
+            .context("Invalid MongoDB URI")?;
 
-        let client = Client::with_uri_str(uri).await.unwrap();
-        let passphrase = var("PACKHUB_SIGN_PASSPHRASE").unwrap().into();
+        let passphrase: Password = config.keys.passphrase.clone().into();
+        let (pgp_key, rsa_key) = (&config.keys.pgp_key, &config.keys.rsa_key);
 
         let cert = if generate_keys {
-            generate_and_save_keys(&passphrase).unwrap()
+            generate_and_save_keys(&passphrase, pgp_key)
         } else {
-            load_cert_from_file().unwrap()
-        };
-
-        let pat = var("PACKHUB_GITHUB_PAT").unwrap();
+            load_cert_from_file(pgp_key)
+        }
+        .with_context(|| format!("Unable to load the OpenPGP key {}", pgp_key.display()))?;
 
-        let github = if pat != "" {
-            OctocrabBuilder::default()
-                .personal_token(var("PACKHUB_GITHUB_PAT").unwrap())
-                .build()
-                .unwrap()
+        // Deployments set up before apk repositories were supported only have the OpenPGP key.
+        let rsa_key = if generate_keys || !rsa_key.exists() {
+            generate_and_save_rsa_key(&passphrase, rsa_key)
         } else {
-            OctocrabBuilder::default().build().unwrap()
+            load_rsa_key_from_file(&passphrase, rsa_key)
+        }
+        .with_context(|| format!("Unable to load the RSA key {}", rsa_key.display()))?;
+
+        // The releases saved by an earlier refresh, if any, are used until the next one.
+        let saved = AptReleases::retrieve_from(&apt_releases_collection(&db))
+            .await
+            .unwrap_or_else(|e| {
+                warn!("Unable to retrieve the saved apt releases: {e}");
+                None
+            });
+        let apt_platform =
+            AptPlatformDetection::new(saved.as_ref().map_or(&[], |saved| saved.releases()));
+
+        let state = Self::new(config, db, (cert, rsa_key), passphrase, apt_platform)?;
+
+        if let Some(interval) = config.apt.releases_refresh() {
+            tokio::spawn(refresh_apt_releases(state.clone(), interval));
+        }
+
+        Ok(state)
+    }
+
+    fn new(
+        config: &Config,
+        db: Client,
+        (cert, rsa_key): (Cert, RsaPrivateKey),
+        passphrase: Password,
+        apt_platform: AptPlatformDetection,
+    ) -> Result<Self> {
+        let token = |token: &Option<String>| token.clone().filter(|token| !token.is_empty());
+        let ttl = config.releases.cache_ttl();
+
+        let github = match token(&config.github.token) {
+            Some(token) => OctocrabBuilder::default().personal_token(token).build()?,
+            None => OctocrabBuilder::default().build()?,
         };
 
-        Self {
+        let gitlab_url = config.gitlab.url.as_deref().unwrap_or("https://gitlab.com");
+        let gitea_url = config
+            .gitea
+            .url
+            .as_deref()
+            .unwrap_or("https://codeberg.org");
+
+        // Index files depend on the listed architectures and are signed with the keys.
+        let apt_architectures = config.apt.architectures()?;
+        let render_config = hashsum::<Sha256>(
+            format!(
+                "{apt_architectures:?}\n{}\n{}",
+                cert.fingerprint(),
+                rsa_key.to_public_key().to_public_key_pem(LineEnding::LF)?
+            )
+            .as_bytes(),
+        );
+
+        let packages = &config.packages;
+        let blob_store_size = packages.blob_store_bytes()?;
+        let blob_store = packages
+            .blob_dir
+            .as_ref()
+            .map(|dir| Arc::new(BlobStore::new(dir, blob_store_size)));
+
+        Ok(Self {
             state: Arc::new(InnerState {
-                db: client,
+                db,
                 cert,
-                github,
+                rsa_key,
+                github: GitHub::new(github, ttl),
+                gitlab: GitLab::new(gitlab_url, token(&config.gitlab.token), ttl)?,
+                gitea: Gitea::new(gitea_url, token(&config.gitea.token), ttl)?,
                 passphrase,
+                base_url: config.base_url.trim_end_matches('/').to_owned(),
+                all_releases: config.releases.all,
+                webhook_secret: token(&config.github.webhook_secret),
+                apt_platform: RwLock::new(Arc::new(apt_platform)),
+                apt_architectures,
+                render_config,
+                max_asset_size: packages.max_asset_bytes()?,
+                blob_store,
+                download_modes: DownloadModes {
+                    default: packages.download_mode,
+                    repositories: packages
+                        .repository_download_modes
+                        .iter()
+                        .map(|(project, mode)| (project.to_lowercase(), *mode))
+                        .collect(),
+                },
             }),
-        }
+        })
     }
 
     /// Get a reference to the MongoDB client.
@@ -66,9 +171,69 @@ impl AppState {
         &self.state.db
     }
 
-    /// Get a reference to the GitHub client.
-    pub fn github(&self) -> &Octocrab {
-        &self.state.github
+    /// Get the release source of the `forge`.
+    pub fn source(&self, forge: Forge) -> Source<'_> {
+        match forge {
+            Forge::Github => Source::GitHub(&self.state.github),
+            Forge::Gitlab => Source::GitLab(&self.state.gitlab),
+            Forge::Gitea => Source::Gitea(&self.state.gitea),
+        }
+    }
+
+    /// Public URL of the deployment, which the installation scripts point to.
+    pub fn base_url(&self) -> &str {
+        &self.state.base_url
+    }
+
+    /// Number of recent releases served by the `all` channel.
+    pub fn all_releases(&self) -> usize {
+        self.state.all_releases
+    }
+
+    /// Secret shared with GitHub to sign webhook deliveries, if webhooks are enabled.
+    pub fn webhook_secret(&self) -> Option<&str> {
+        self.state.webhook_secret.as_deref()
+    }
+
+    /// Detection of the Ubuntu and Debian releases of apt clients.
+    pub fn apt_platform(&self) -> Arc<AptPlatformDetection> {
+        self.state.apt_platform.read().unwrap().clone()
+    }
+
+    pub fn set_apt_platform(&self, platform: AptPlatformDetection) {
+        *self.state.apt_platform.write().unwrap() = Arc::new(platform);
+    }
+
+    /// Architectures listed by every apt repository, whether they have packages or not.
+    pub fn apt_architectures(&self) -> &[Arch] {
+        &self.state.apt_architectures
+    }
+
+    /// Fingerprint of the configuration the index files are rendered with.
+    pub fn render_config(&self) -> &str {
+        &self.state.render_config
+    }
+
+    /// Maximum size of the downloaded packages, in bytes.
+    pub fn max_asset_size(&self) -> u64 {
+        self.state.max_asset_size
+    }
+
+    /// Store of the downloaded packages, if it's enabled.
+    pub fn blob_store(&self) -> Option<Arc<BlobStore>> {
+        self.state.blob_store.clone()
+    }
+
+    /// How the packages of the repository are delivered to the clients.
+    pub fn download_mode(&self, forge: Forge, owner: &str, repo: &str) -> DownloadMode {
+        let modes = &self.state.download_modes;
+        let project = format!("{forge}/{owner}/{repo}").to_lowercase();
+
+        modes
+            .repositories
+            .get(&project)
+            .copied()
+            .unwrap_or(modes.default)
     }
 
     pub fn clearsign_metadata(&self, data: &str) -> Result<Vec<u8>> {
@@ -79,6 +244,25 @@ impl AppState {
         detached_sign_metadata(data, &self.state.cert, &self.state.passphrase)
     }
 
+    pub fn detached_sign_data(&self, data: &[u8]) -> Result<Vec<u8>> {
+        detached_sign_data(data, &self.state.cert, &self.state.passphrase)
+    }
+
+    /// Sign the `data` with the RSA key of the apk repositories.
+    pub fn rsa_sign(&self, data: &[u8]) -> Vec<u8> {
+        rsa_sign(data, &self.state.rsa_key)
+    }
+
+    /// PEM encoded public RSA key, which verifies the apk indices.
+    pub fn rsa_public_key(&self) -> Vec<u8> {
+        self.state
+            .rsa_key
+            .to_public_key()
+            .to_public_key_pem(LineEnding::LF)
+            .unwrap()
+            .into_bytes()
+    }
+
     pub fn armored_public_key(&self) -> Vec<u8> {
         self.state.cert.armored().to_vec().unwrap()
     }
@@ -88,6 +272,13 @@ impl AppState {
     }
 }
 
+/// Download modes of the deployment and of individual repositories.
+struct DownloadModes {
+    default: DownloadMode,
+    /// Modes of the repositories (`forge/owner/repo`, in lowercase) overriding the default.
+    repositories: HashMap<String, DownloadMode>,
+}
+
 #[cfg(test)]
 static INIT: std::sync::Once = std::sync::Once::new();
 
@@ -100,29 +291,32 @@ impl AppState {
                 .unwrap();
         });
 
-        let uri = format!(
-            "mongodb://{}:{}@{}:27017",
-            var("PACKHUB_DB_USER").unwrap(),
-            var("PACKHUB_DB_PASSWORD").unwrap(),
-            var("PACKHUB_DB_HOST").unwrap()
-        );
+        let config = Config::load(None).unwrap();
+        let db = Client::with_uri_str(config.database.uri().unwrap())
+            .await
+            .unwrap();
+        let passphrase: Password = config.keys.passphrase.clone().into();
+        let (pgp_key, rsa_key) = (&config.keys.pgp_key, &config.keys.rsa_key);
 
-        let db = Client::with_uri_str(uri).await.unwrap();
-        let passphrase = var("PACKHUB_SIGN_PASSPHRASE").unwrap().into();
+        let cert = if pgp_key.exists() {
+            load_cert_from_file(pgp_key).unwrap()
+        } else {
+            generate_and_save_keys(&passphrase, pgp_key).unwrap()
+        };
 
-        let cert = if std::path::Path::new("key.gpg").exists() {
-            load_cert_from_file().unwrap()
+        let rsa_key = if rsa_key.exists() {
+            load_rsa_key_from_file(&passphrase, rsa_key).unwrap()
         } else {
-            generate_and_save_keys(&passphrase).unwrap()
+            generate_and_save_rsa_key(&passphrase, rsa_key).unwrap()
         };
 
-        Self {
-            state: Arc::new(InnerState {
-                db,
-                cert,
-                github: OctocrabBuilder::default().build().unwrap(),
-                passphrase,
-            }),
-        }
+        Self::new(
+            &config,
+            db,
+            (cert, rsa_key),
+            passphrase,
+            AptPlatformDetection::new(&[]),
+        )
+        .unwrap()
     }
 }
@@ -1,8 +1,17 @@
-use std::{fmt::Display, ops::Add, str::FromStr};
+use std::{
+    collections::BTreeMap,
+    fmt::Display,
+    io::{Read, Write},
+    ops::Add,
+    str::FromStr,
+};
 
-use anyhow::Result;
+use anyhow::{Result, bail};
+use bzip2::read::BzDecoder;
 use lenient_semver::parse;
-use semver::Version;
+use libflate::gzip::{Decoder, EncodeOptions, Encoder, HeaderBuilder};
+use liblzma::read::XzDecoder;
+use semver::{Version, VersionReq};
 use serde::Deserialize;
 use sha1::digest::{Digest, OutputSizeUser, generic_array::ArrayLength};
 
@@ -13,6 +22,14 @@ pub enum Dist {
     Fedora(Option<Version>),
     Tumbleweed,
     Leap(Option<Version>),
+    Arch,
+    Alpine(Option<Version>),
+    Rhel(Option<Version>),
+    Rocky(Option<Version>),
+    Alma(Option<Version>),
+    CentOS(Option<Version>),
+    AmazonLinux(Option<Version>),
+    Mageia(Option<Version>),
 }
 
 impl Dist {
@@ -26,16 +43,50 @@ impl Dist {
             Dist::Fedora(_) => matches!(dist, Dist::Fedora(_)),
             Dist::Tumbleweed => matches!(dist, Dist::Tumbleweed),
             Dist::Leap(_) => matches!(dist, Dist::Leap(_)),
+            Dist::Arch => matches!(dist, Dist::Arch),
+            Dist::Alpine(_) => matches!(dist, Dist::Alpine(_)),
+            // Packages built for RHEL can be installed on its rebuilds.
+            Dist::Rhel(_) | Dist::Rocky(_) | Dist::Alma(_) | Dist::CentOS(_) => matches!(
+                dist,
+                Dist::Rhel(_) | Dist::Rocky(_) | Dist::Alma(_) | Dist::CentOS(_)
+            ),
+            Dist::AmazonLinux(_) => matches!(dist, Dist::AmazonLinux(_)),
+            Dist::Mageia(_) => matches!(dist, Dist::Mageia(_)),
+        }
+    }
+
+    /// Version of the distribution.
+    pub fn version(&self) -> Option<&Version> {
+        match self {
+            Dist::Tumbleweed | Dist::Arch => None,
+            Dist::Ubuntu(ver)
+            | Dist::Debian(ver)
+            | Dist::Fedora(ver)
+            | Dist::Leap(ver)
+            | Dist::Alpine(ver)
+            | Dist::Rhel(ver)
+            | Dist::Rocky(ver)
+            | Dist::Alma(ver)
+            | Dist::CentOS(ver)
+            | Dist::AmazonLinux(ver)
+            | Dist::Mageia(ver) => ver.as_ref(),
         }
     }
 
     pub fn set_version(&mut self, version: Option<&str>) {
         match self {
-            Dist::Debian(ver) => *ver = version.and_then(|v| parse(v).ok()),
-            Dist::Ubuntu(ver) => *ver = version.and_then(|v| parse(v).ok()),
-            Dist::Fedora(ver) => *ver = version.and_then(|v| parse(v).ok()),
-            Dist::Tumbleweed => {}
-            Dist::Leap(ver) => *ver = version.and_then(|v| parse(v).ok()),
+            Dist::Tumbleweed | Dist::Arch => {}
+            Dist::Ubuntu(ver)
+            | Dist::Debian(ver)
+            | Dist::Fedora(ver)
+            | Dist::Leap(ver)
+            | Dist::Alpine(ver)
+            | Dist::Rhel(ver)
+            | Dist::Rocky(ver)
+            | Dist::Alma(ver)
+            | Dist::CentOS(ver)
+            | Dist::AmazonLinux(ver)
+            | Dist::Mageia(ver) => *ver = version.and_then(|v| parse(v).ok()),
         }
     }
 
@@ -47,15 +98,50 @@ impl Dist {
         Dist::Debian(parse(version).ok())
     }
 
+    #[cfg(test)]
     pub fn fedora(version: &str) -> Self {
         Dist::Fedora(parse(version).ok())
     }
 
+    pub fn rhel(version: &str) -> Self {
+        Dist::Rhel(parse(version).ok())
+    }
+
+    pub fn alpine(version: &str) -> Self {
+        Dist::Alpine(parse(version).ok())
+    }
+
+    #[cfg(test)]
     pub fn leap(version: &str) -> Self {
         Dist::Leap(parse(version).ok())
     }
 }
 
+impl Display for Dist {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let (name, version) = match self {
+            Dist::Ubuntu(ver) => ("ubuntu", ver),
+            Dist::Debian(ver) => ("debian", ver),
+            Dist::Fedora(ver) => ("fedora", ver),
+            Dist::Tumbleweed => ("tumbleweed", &None),
+            Dist::Leap(ver) => ("leap", ver),
+            Dist::Arch => ("arch", &None),
+            Dist::Alpine(ver) => ("alpine", ver),
+            Dist::Rhel(ver) => ("rhel", ver),
+            Dist::Rocky(ver) => ("rocky", ver),
+            Dist::Alma(ver) => ("alma", ver),
+            Dist::CentOS(ver) => ("centos", ver),
+            Dist::AmazonLinux(ver) => ("amazonlinux", ver),
+            Dist::Mageia(ver) => ("mageia", ver),
+        };
+
+        match version {
+            Some(version) => write!(f, "{name}-{version}"),
+            None => write!(f, "{name}"),
+        }
+    }
+}
+
 #[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Hash, Default)]
 pub enum Arch {
     #[default]
@@ -67,6 +153,11 @@ pub enum Arch {
     PPC64le,
     RiscV64,
     S390x,
+    I386,
+    /// Architecture independent packages.
+    Noarch,
+    Loong64,
+    Mips64el,
 }
 
 impl FromStr for Arch {
@@ -83,8 +174,18 @@ impl FromStr for Arch {
             "armv7" => Ok(Arch::Armv7),
             "armv7l" => Ok(Arch::Armv7),
             "ppc64le" => Ok(Arch::PPC64le),
+            "ppc64el" => Ok(Arch::PPC64le),
             "riscv64" => Ok(Arch::RiscV64),
             "s390x" => Ok(Arch::S390x),
+            "armhfp" => Ok(Arch::Armv7),
+            "armv7hl" => Ok(Arch::Armv7),
+            "i386" => Ok(Arch::I386),
+            "i686" => Ok(Arch::I386),
+            "noarch" => Ok(Arch::Noarch),
+            "all" => Ok(Arch::Noarch),
+            "loong64" => Ok(Arch::Loong64),
+            "loongarch64" => Ok(Arch::Loong64),
+            "mips64el" => Ok(Arch::Mips64el),
             _ => Err(()),
         }
     }
@@ -103,6 +204,28 @@ impl Display for Arch {
             Arch::PPC64le => write!(f, "ppc64el"),
             Arch::RiscV64 => write!(f, "riscv64"),
             Arch::S390x => write!(f, "s390x"),
+            Arch::I386 => write!(f, "i386"),
+            Arch::Noarch => write!(f, "all"),
+            Arch::Loong64 => write!(f, "loong64"),
+            Arch::Mips64el => write!(f, "mips64el"),
+        }
+    }
+}
+
+impl Arch {
+    /// Name of the architecture in the naming convention of RPM.
+    pub fn rpm_name(&self) -> &'static str {
+        match self {
+            Arch::Amd64 => "x86_64",
+            Arch::Arm64 | Arch::Aarch64 => "aarch64",
+            Arch::Armhf | Arch::Armv7 => "armv7hl",
+            Arch::PPC64le => "ppc64le",
+            Arch::RiscV64 => "riscv64",
+            Arch::S390x => "s390x",
+            Arch::I386 => "i686",
+            Arch::Noarch => "noarch",
+            Arch::Loong64 => "loongarch64",
+            Arch::Mips64el => "mips64el",
         }
     }
 }
@@ -111,13 +234,28 @@ impl Display for Arch {
 pub enum Type {
     Deb,
     Rpm,
+    Pacman,
+    Apk,
 }
 
 impl Type {
     pub fn matches_distribution(&self, dist: &Dist) -> bool {
         match self {
             Type::Deb => matches!(dist, Dist::Debian(_) | Dist::Ubuntu(_)),
-            Type::Rpm => matches!(dist, Dist::Fedora(_) | Dist::Tumbleweed),
+            Type::Rpm => matches!(
+                dist,
+                Dist::Fedora(_)
+                    | Dist::Tumbleweed
+                    | Dist::Leap(_)
+                    | Dist::Rhel(_)
+                    | Dist::Rocky(_)
+                    | Dist::Alma(_)
+                    | Dist::CentOS(_)
+                    | Dist::AmazonLinux(_)
+                    | Dist::Mageia(_)
+            ),
+            Type::Pacman => matches!(dist, Dist::Arch),
+            Type::Apk => matches!(dist, Dist::Alpine(_)),
         }
     }
 }
@@ -130,11 +268,102 @@ where
     format!("{:x}", T::digest(data))
 }
 
-#[derive(Debug, Deserialize)]
-#[serde(rename_all = "lowercase")]
+/// Compress the `data` with gzip.
+///
+/// The modification time is left out of the header, so that the output is reproducible.
+pub fn gzip_compression(data: &[u8]) -> Vec<u8> {
+    let header = HeaderBuilder::new().modification_time(0).finish();
+    let options = EncodeOptions::new().header(header);
+    let mut encoder = Encoder::with_options(Vec::new(), options).unwrap();
+    encoder.write_all(data).unwrap();
+
+    let gzip = encoder.finish();
+
+    gzip.into_result().unwrap()
+}
+
+/// Reader which un-compresses the `reader` compressed as told by its file `extension`.
+///
+/// Supports gzip, xz, zstd and bzip2 compressed or uncompressed data. The data is
+/// un-compressed while it's read.
+pub fn decoder<'a>(extension: &str, reader: impl Read + 'a) -> Result<Box<dyn Read + 'a>> {
+    Ok(match extension {
+        "" => Box::new(reader),
+        ".gz" => Box::new(Decoder::new(reader)?),
+        ".xz" => Box::new(XzDecoder::new_multi_decoder(reader)),
+        ".zst" => Box::new(zstd::Decoder::new(reader)?),
+        ".bz2" => Box::new(BzDecoder::new(reader)),
+        extension => bail!("Unsupported compression: {extension}"),
+    })
+}
+
+/// Fields of a `.PKGINFO` file, which describes pacman and apk packages.
+///
+/// Fields like `depend` can be repeated.
+pub type PkgInfo = BTreeMap<String, Vec<String>>;
+
+/// Parse the `key = value` lines of a `.PKGINFO` file.
+pub fn parse_pkginfo(content: &str) -> PkgInfo {
+    let mut info = PkgInfo::new();
+
+    for line in content.lines() {
+        if line.starts_with('#') {
+            continue;
+        }
+
+        if let Some((key, value)) = line.split_once('=') {
+            info.entry(key.trim().to_owned())
+                .or_default()
+                .push(value.trim().to_owned());
+        }
+    }
+
+    info
+}
+
+/// Release channel of a repository.
+///
+/// It is the apt suite or the rpm channel segment of the repository routes:
+/// * `stable`: The latest stable release.
+/// * `unstable`: The latest release, including pre-releases.
+/// * `all`: Every recent stable release, so that older versions can be installed.
+/// * A version requirement starting with an operator (`~2.0`, `^1`, `>=1.2,<2`): The newest release
+///   whose tag matches the requirement.
+/// * Anything else (`v2.0.0`, or `tag-2.0.0` to avoid ambiguity): The release with that tag.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(try_from = "String")]
 pub enum ReleaseChannel {
     Stable,
     Unstable,
+    All,
+    Tag(String),
+    Range(VersionReq),
+}
+
+impl FromStr for ReleaseChannel {
+    type Err = semver::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "stable" => Ok(ReleaseChannel::Stable),
+            "unstable" => Ok(ReleaseChannel::Unstable),
+            "all" => Ok(ReleaseChannel::All),
+            s if s.starts_with(['~', '^', '=', '>', '<', '*']) => {
+                Ok(ReleaseChannel::Range(VersionReq::parse(s)?))
+            }
+            s => Ok(ReleaseChannel::Tag(
+                s.strip_prefix("tag-").unwrap_or(s).to_owned(),
+            )),
+        }
+    }
+}
+
+impl TryFrom<String> for ReleaseChannel {
+    type Error = semver::Error;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        value.parse()
+    }
 }
 
 impl Display for ReleaseChannel {
@@ -142,10 +371,23 @@ impl Display for ReleaseChannel {
         match self {
             ReleaseChannel::Stable => write!(f, "stable"),
             ReleaseChannel::Unstable => write!(f, "unstable"),
+            ReleaseChannel::All => write!(f, "all"),
+            // Keep the tag distinguishable from the other channels.
+            ReleaseChannel::Tag(tag) if tag.parse().ok().as_ref() != Some(self) => {
+                write!(f, "tag-{tag}")
+            }
+            ReleaseChannel::Tag(tag) => write!(f, "{tag}"),
+            // Spaces aren't allowed in apt suites.
+            ReleaseChannel::Range(req) => write!(f, "{}", req.to_string().replace(' ', "")),
         }
     }
 }
 
+/// Parse the version from the tag of a release (`v1.2.3`, `1.2`).
+pub fn tag_version(tag: &str) -> Option<Version> {
+    parse(tag).ok()
+}
+
 #[derive(Debug, Deserialize)]
 #[serde(rename_all = "lowercase")]
 pub enum AppVersion {
@@ -173,12 +415,95 @@ mod tests {
         assert!(!Type::Deb.matches_distribution(&Dist::Fedora(None)));
         assert!(Type::Rpm.matches_distribution(&Dist::Fedora(None)));
         assert!(Type::Rpm.matches_distribution(&Dist::Tumbleweed));
+        assert!(Type::Rpm.matches_distribution(&Dist::leap("15.5")));
+        assert!(Type::Rpm.matches_distribution(&Dist::Alma(None)));
+        assert!(Type::Pacman.matches_distribution(&Dist::Arch));
+        assert!(!Type::Pacman.matches_distribution(&Dist::Tumbleweed));
+        assert!(Type::Apk.matches_distribution(&Dist::alpine("3.20")));
     }
 
     #[test]
     fn test_dist_matches() {
         assert!(Dist::Ubuntu(None).matches_distribution(&Dist::ubuntu("24.04")));
         assert!(!Dist::Debian(None).matches_distribution(&Dist::ubuntu("24.04")));
+        assert!(Dist::Rocky(None).matches_distribution(&Dist::rhel("9")));
+        assert!(!Dist::AmazonLinux(None).matches_distribution(&Dist::rhel("9")));
+    }
+
+    #[test]
+    fn test_dist_display() {
+        assert_eq!(Dist::ubuntu("24.04").to_string(), "ubuntu-24.4.0");
+        assert_eq!(Dist::Debian(None).to_string(), "debian");
+        assert_eq!(Dist::Tumbleweed.to_string(), "tumbleweed");
+        assert_eq!(Dist::Arch.to_string(), "arch");
+        assert_eq!(Dist::alpine("3.20").to_string(), "alpine-3.20.0");
+    }
+
+    #[test]
+    fn test_arch_naming() {
+        let arch = |name: &str| name.parse::<Arch>().unwrap();
+
+        // Debian and RPM names of the same architecture
+        for (debian, rpm) in [
+            ("amd64", "x86_64"),
+            ("arm64", "aarch64"),
+            ("armhf", "armv7hl"),
+            ("ppc64el", "ppc64le"),
+            ("i386", "i686"),
+            ("all", "noarch"),
+            ("loong64", "loongarch64"),
+            ("mips64el", "mips64el"),
+        ] {
+            assert_eq!(arch(debian).to_string(), debian);
+            assert_eq!(arch(rpm).to_string(), debian);
+            assert_eq!(arch(debian).rpm_name(), rpm);
+            assert_eq!(arch(rpm).rpm_name(), rpm);
+        }
+
+        // The `$basearch` of 32-bit ARM
+        assert_eq!(arch("armhfp").rpm_name(), "armv7hl");
+    }
+
+    #[test]
+    fn test_release_channel() {
+        let parse = |s: &str| s.parse::<ReleaseChannel>().ok();
+
+        assert_eq!(parse("stable"), Some(ReleaseChannel::Stable));
+        assert_eq!(parse("unstable"), Some(ReleaseChannel::Unstable));
+        assert_eq!(parse("all"), Some(ReleaseChannel::All));
+        assert_eq!(
+            parse("v2.0.0"),
+            Some(ReleaseChannel::Tag("v2.0.0".to_owned()))
+        );
+        assert_eq!(
+            parse("tag-2.0.0"),
+            Some(ReleaseChannel::Tag("2.0.0".to_owned()))
+        );
+        assert_eq!(
+            parse("~2.0"),
+            Some(ReleaseChannel::Range(VersionReq::parse("~2.0").unwrap()))
+        );
+        assert!(">=1.2,<".parse::<ReleaseChannel>().is_err());
+
+        // Display round-trips
+        for channel in [
+            "stable",
+            "all",
+            "v2.0.0",
+            "tag-all",
+            "tag-tag-1",
+            ">=1.2,<2",
+        ] {
+            let parsed: ReleaseChannel = channel.parse().unwrap();
+            assert_eq!(parsed.to_string(), channel);
+        }
+    }
+
+    #[test]
+    fn test_tag_version() {
+        assert_eq!(tag_version("v1.2.3"), Some(Version::new(1, 2, 3)));
+        assert_eq!(tag_version("2.0"), Some(Version::new(2, 0, 0)));
+        assert_eq!(tag_version("nightly"), None);
     }
 
     #[test]
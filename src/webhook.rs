@@ -0,0 +1,140 @@
+//! Webhooks through which forges notify packhub of new releases.
+use anyhow::{Context, Result};
+use axum::{
+    Router,
+    body::Bytes,
+    extract::State,
+    http::{HeaderMap, StatusCode},
+    routing::post,
+};
+use hmac::{Hmac, Mac};
+use serde::Deserialize;
+use sha2::Sha256;
+use tracing::{error, info};
+
+use crate::{
+    error::AppError, repository::Repository, source::Forge, state::AppState, utils::ReleaseChannel,
+};
+
+/// Payload of a GitHub `release` event (only the fields we need).
+#[derive(Deserialize)]
+struct ReleaseEvent {
+    action: String,
+    release: EventRelease,
+    repository: EventRepository,
+}
+
+#[derive(Deserialize)]
+struct EventRelease {
+    tag_name: String,
+}
+
+#[derive(Deserialize)]
+struct EventRepository {
+    name: String,
+    owner: EventOwner,
+}
+
+#[derive(Deserialize)]
+struct EventOwner {
+    login: String,
+}
+
+/// Verify the `X-Hub-Signature-256` header (`sha256=<hex HMAC of the body>`) of a delivery.
+fn verify_signature(secret: &str, body: &[u8], signature: Option<&str>) -> bool {
+    let Some(signature) = signature
+        .and_then(|signature| signature.strip_prefix("sha256="))
+        .and_then(|signature| hex::decode(signature).ok())
+    else {
+        return false;
+    };
+
+    let mut mac =
+        Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC accepts keys of any size");
+    mac.update(body);
+
+    // Constant time comparison
+    mac.verify_slice(&signature).is_ok()
+}
+
+#[tracing::instrument(name = "GitHub webhook", skip_all)]
+async fn github_webhook(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Result<(StatusCode, &'static str), AppError> {
+    let Some(secret) = state.webhook_secret() else {
+        return Ok((StatusCode::NOT_FOUND, "Webhooks are not enabled"));
+    };
+
+    let header = |name| headers.get(name).and_then(|value| value.to_str().ok());
+
+    if !verify_signature(secret, &body, header("X-Hub-Signature-256")) {
+        return Ok((StatusCode::UNAUTHORIZED, "Invalid signature"));
+    }
+
+    match header("X-GitHub-Event") {
+        Some("release") => {}
+        Some("ping") => return Ok((StatusCode::OK, "pong")),
+        _ => return Ok((StatusCode::ACCEPTED, "Event ignored")),
+    }
+
+    let event: ReleaseEvent =
+        serde_json::from_slice(&body).context("Unable to parse the release event")?;
+    let owner = event.repository.owner.login;
+    let repo = event.repository.name;
+
+    // Any change of a release can change the release listings.
+    state.source(Forge::Github).invalidate(&owner, &repo);
+
+    if event.action != "published" {
+        return Ok((StatusCode::ACCEPTED, "Release listings refreshed"));
+    }
+
+    let tag = event.release.tag_name;
+    info!("Pre-warming release {tag} of {owner}/{repo}");
+
+    // Respond right away, GitHub gives up on deliveries after 10 seconds.
+    tokio::spawn(async move {
+        if let Err(e) = prewarm(&state, &owner, &repo, tag).await {
+            error!("Failed to pre-warm {owner}/{repo}: {e}");
+        }
+    });
+
+    Ok((StatusCode::ACCEPTED, "Release is being pre-warmed"))
+}
+
+/// Extract and save the metadata of every package of the release tagged with `tag`.
+async fn prewarm(state: &AppState, owner: &str, repo: &str, tag: String) -> Result<()> {
+    let channel = ReleaseChannel::Tag(tag);
+    let mut repository =
+        Repository::from_forge(Forge::Github, owner, repo, &channel, state).await?;
+
+    repository.prewarm().await?;
+    info!("Pre-warmed release {channel} of {owner}/{repo}");
+
+    Ok(())
+}
+
+pub fn webhook_routes() -> Router<AppState> {
+    Router::new().route("/github", post(github_webhook))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_signature_verification() {
+        // Example from the GitHub documentation on validating webhook deliveries
+        let secret = "It's a Secret to Everybody";
+        let body = b"Hello, World!";
+        let signature = "sha256=757107ea0eb2509fc211221cce984b8a37570b6d7586c22c46f4379c8b043e17";
+
+        assert!(verify_signature(secret, body, Some(signature)));
+        assert!(!verify_signature(secret, b"Hello, World?", Some(signature)));
+        assert!(!verify_signature("secret", body, Some(signature)));
+        assert!(!verify_signature(secret, body, Some(&signature[7..])));
+        assert!(!verify_signature(secret, body, None));
+    }
+}
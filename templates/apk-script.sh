@@ -0,0 +1,16 @@
+#!/bin/sh
+
+echo "Welcome to package key and repository setup script for {{repo}}"
+echo "This script will add the repository key and repository to your system."
+echo "Please make sure you run this script as root."
+echo
+echo "Downloading and installing the repository key..."
+wget -qO /etc/apk/keys/packhub.rsa.pub "{{host}}/v1/keys/packhub.rsa.pub"
+echo
+echo "Adding the repository to your system..."
+release=$(cut -d. -f1,2 /etc/alpine-release)
+repository="{{host}}/v1/apk/{{forge}}/{{owner}}/{{repo}}/{{channel}}/v$release"
+grep -qxF "$repository" /etc/apk/repositories || echo "$repository" >> /etc/apk/repositories
+echo
+echo "Updating package lists..."
+apk update
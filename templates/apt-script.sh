@@ -5,10 +5,18 @@ echo "This script will add the repository key and repository to your system."
 echo "Please make sure you have sudo access to run this script."
 echo
 echo "Downloading and installing the repository key..."
-wget -qO- {{host}}/v1/keys/packhub.gpg | sudo tee /etc/apt/keyrings/packhub.gpg > /dev/null
+wget -qO- "{{host}}/v1/keys/packhub.gpg" | sudo tee /etc/apt/keyrings/packhub.gpg > /dev/null
 echo
 echo "Adding the repository to your system..."
-echo "deb [signed-by=/etc/apt/keyrings/packhub.gpg] {{host}}/v1/apt/{{distro}}/github/{{owner}}/{{repo}} {{channel}} main" | sudo tee /etc/apt/sources.list.d/{{repo}}.list > /dev/null
+# The release in the repository path spares the detection of the distribution from the
+# version of apt, which fails on derivatives like Linux Mint or Raspberry Pi OS.
+. /etc/os-release
+{% if distro == "ubuntu" -%}
+RELEASE="${UBUNTU_CODENAME:-$VERSION_CODENAME}"
+{%- else -%}
+RELEASE="${DEBIAN_CODENAME:-$VERSION_CODENAME}"
+{%- endif %}
+echo "deb [signed-by=/etc/apt/keyrings/packhub.gpg] {{host}}/v1/apt/{{distro}}${RELEASE:+/$RELEASE}/{{forge}}/{{owner}}/{{repo}} {{channel}} main" | sudo tee "/etc/apt/sources.list.d/{{repo}}.list" > /dev/null
 echo 
 echo "Updating package lists..."
 sudo apt-get update
@@ -0,0 +1,17 @@
+#!/bin/sh
+
+echo "Welcome to package key and repository setup script for {{repo}}"
+echo "This script will add the repository key and repository to your system."
+echo "Please make sure you have sudo access to run this script."
+echo
+echo "Downloading and installing the repository key..."
+curl -fsSL "{{host}}/v1/keys/packhub.asc" | sudo pacman-key --add -
+sudo pacman-key --lsign-key sign@packhub.dev
+echo
+echo "Adding the repository to your system..."
+if ! grep -qxF "[{{repo_name()}}]" /etc/pacman.conf; then
+    echo -e "\n[{{repo_name()}}]\nSigLevel = PackageOptional DatabaseRequired\nServer = {{host}}/v1/pacman/{{forge}}/{{owner}}/{{repo}}/{{channel}}/\$arch" | sudo tee -a /etc/pacman.conf > /dev/null
+fi
+echo
+echo "Updating package lists..."
+sudo pacman -Sy